@@ -0,0 +1,415 @@
+//! Relay outputs switched by alarm rules or thermostat/humidistat control
+//! loops, with safety interlocks
+//!
+//! The controller is independent of the GPIO, the relay only maps its
+//! state onto a pin.
+
+use crate::alarm::{Channel, RulesEngine, Severity};
+use crate::sensor::Measurement;
+use crate::system_clock::SystemClock;
+use crate::warm_up::WarmUp;
+use core::fmt;
+use embedded_hal::digital::v2::{OutputPin, PinState};
+use embedded_time::{duration::Seconds, Instant};
+
+/// One per relay output
+pub const MAX_ACTUATORS: usize = 3;
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Direction {
+    /// On below the setpoint, e.g. a heater or humidifier
+    Raise,
+    /// On above the setpoint, e.g. an air conditioner or dehumidifier
+    Lower,
+}
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub enum Control {
+    /// On while an alarm rule of at least min_severity is active, on the
+    /// channel or on any channel if None
+    Alarm {
+        channel: Option<Channel>,
+        min_severity: Severity,
+    },
+    /// Switches on once the value is hysteresis past the setpoint and off
+    /// again when it's back at the setpoint
+    Loop {
+        channel: Channel,
+        direction: Direction,
+        setpoint: f32,
+        hysteresis: f32,
+    },
+}
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub struct ActuatorConfig {
+    /// Record column name
+    pub name: &'static str,
+    pub control: Control,
+    /// Minimum time between switching, protects compressors and relays
+    pub min_cycle_time: Seconds,
+    /// Switched off after being on this long, and stays off until the
+    /// control no longer demands it. None for no limit.
+    pub max_on_time: Option<Seconds>,
+    /// The relay module switches on with a low output
+    pub active_low: bool,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Reason {
+    Control,
+    MaxOnTime,
+    SensorFailure,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct Actuation {
+    pub on: bool,
+    pub reason: Reason,
+}
+
+impl fmt::Display for Actuation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self.reason {
+            Reason::Control => "control",
+            Reason::MaxOnTime => "max on-time",
+            Reason::SensorFailure => "sensor failure",
+        };
+        write!(f, "{} ({})", if self.on { "on" } else { "off" }, reason)
+    }
+}
+
+fn elapsed(now: &Instant<SystemClock>, since: &Instant<SystemClock>, duration: Seconds) -> bool {
+    match now.checked_duration_since(since) {
+        Some(dur) => dur >= duration.into(),
+        None => false,
+    }
+}
+
+pub struct Actuator {
+    config: ActuatorConfig,
+    on: bool,
+    last_change: Option<Instant<SystemClock>>,
+    /// Max on-time interlock tripped
+    locked_out: bool,
+}
+
+impl Actuator {
+    pub fn new(config: ActuatorConfig) -> Self {
+        Actuator {
+            config,
+            on: false,
+            last_change: None,
+            locked_out: false,
+        }
+    }
+
+    pub fn config(&self) -> &ActuatorConfig {
+        &self.config
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    /// Call with each new measurement, returns the change if switched
+    pub fn update(
+        &mut self,
+        now: &Instant<SystemClock>,
+        data: &Measurement,
+        rules: &RulesEngine,
+        warm_up: &WarmUp,
+    ) -> Option<Actuation> {
+        if let Some(actuation) = self.check_max_on_time(now) {
+            return Some(actuation);
+        }
+
+        let demand = self.demand(now, data, rules, warm_up);
+        if self.locked_out {
+            self.locked_out = demand;
+            return None;
+        }
+
+        let cycled = match self.last_change {
+            Some(since) => elapsed(now, &since, self.config.min_cycle_time),
+            None => true,
+        };
+        if demand != self.on && cycled {
+            self.switch(now, demand, Reason::Control)
+        } else {
+            None
+        }
+    }
+
+    /// Call every second, the max on-time interlock can't wait for the next
+    /// measurement
+    pub fn check_max_on_time(&mut self, now: &Instant<SystemClock>) -> Option<Actuation> {
+        if let (true, Some(max_on_time), Some(since)) =
+            (self.on, self.config.max_on_time, self.last_change)
+        {
+            if elapsed(now, &since, max_on_time) {
+                self.locked_out = true;
+                return self.switch(now, false, Reason::MaxOnTime);
+            }
+        }
+        None
+    }
+
+    /// Call when the sensor has failed, switches off immediately
+    pub fn shutdown(&mut self, now: &Instant<SystemClock>) -> Option<Actuation> {
+        self.switch(now, false, Reason::SensorFailure)
+    }
+
+    fn demand(
+        &self,
+        now: &Instant<SystemClock>,
+        data: &Measurement,
+        rules: &RulesEngine,
+        warm_up: &WarmUp,
+    ) -> bool {
+        match self.config.control {
+            Control::Alarm {
+                channel,
+                min_severity,
+            } => rules.active_rules().any(|rule| {
+                channel.map_or(true, |c| c == rule.channel) && rule.severity >= min_severity
+            }),
+            Control::Loop {
+                channel,
+                direction,
+                setpoint,
+                hysteresis,
+            } => {
+                if !warm_up.are_settled(channel.quantities(), now) {
+                    return false;
+                }
+                let value = match channel.value(data) {
+                    Some(value) => value,
+                    None => return self.on,
+                };
+                match (direction, self.on) {
+                    (Direction::Raise, true) => value < setpoint,
+                    (Direction::Raise, false) => value <= setpoint - hysteresis,
+                    (Direction::Lower, true) => value > setpoint,
+                    (Direction::Lower, false) => value >= setpoint + hysteresis,
+                }
+            }
+        }
+    }
+
+    fn switch(
+        &mut self,
+        now: &Instant<SystemClock>,
+        on: bool,
+        reason: Reason,
+    ) -> Option<Actuation> {
+        if on == self.on {
+            return None;
+        }
+        self.on = on;
+        self.last_change.replace(*now);
+        Some(Actuation { on, reason })
+    }
+}
+
+/// Level of a relay pin while off, configure the pin in this state so it
+/// isn't briefly driven on before the Relay takes it over
+pub fn relay_off_state(active_low: bool) -> PinState {
+    if active_low {
+        PinState::High
+    } else {
+        PinState::Low
+    }
+}
+
+/// Drives the relay pin from the actuator state
+pub struct Relay<P> {
+    pin: P,
+    active_low: bool,
+}
+
+impl<P: OutputPin> Relay<P> {
+    /// Starts off
+    pub fn new(pin: P, active_low: bool) -> Self {
+        let mut relay = Relay { pin, active_low };
+        relay.set(false);
+        relay
+    }
+
+    pub fn set(&mut self, on: bool) {
+        // GPIO writes are infallible
+        let _ = if on != self.active_low {
+            self.pin.set_high()
+        } else {
+            self.pin.set_low()
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::warm_up::WarmUpConfig;
+
+    const SETTLED: WarmUpConfig = WarmUpConfig {
+        temperature: Seconds(0),
+        humidity: Seconds(0),
+        pressure: Seconds(0),
+        gas_resistance: Seconds(0),
+    };
+
+    /// On below 17 °C, off again at 18 °C
+    fn heater(min_cycle_time: Seconds, max_on_time: Option<Seconds>) -> Actuator {
+        Actuator::new(ActuatorConfig {
+            name: "heater",
+            control: Control::Loop {
+                channel: Channel::Temperature,
+                direction: Direction::Raise,
+                setpoint: 18.0,
+                hysteresis: 1.0,
+            },
+            min_cycle_time,
+            max_on_time,
+            active_low: false,
+        })
+    }
+
+    fn at(sec: u32) -> Instant<SystemClock> {
+        Instant::new(sec)
+    }
+
+    fn measurement(temperature: f32) -> Measurement {
+        Measurement {
+            temperature,
+            humidity: 50.0,
+            pressure: 1013.25,
+            gas_resistance: None,
+        }
+    }
+
+    /// Feeds (second, temperature) measurements, returns the state after each
+    fn run(actuator: &mut Actuator, trace: &[(u32, f32)]) -> heapless::Vec<bool, 16> {
+        let rules = RulesEngine::new(&[]);
+        let warm_up = WarmUp::new(SETTLED, &at(0));
+        trace
+            .iter()
+            .map(|(sec, t)| {
+                actuator.update(&at(*sec), &measurement(*t), &rules, &warm_up);
+                actuator.is_on()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn loop_hysteresis() {
+        let mut heater = heater(Seconds(0), None);
+        assert_eq!(
+            run(
+                &mut heater,
+                &[
+                    (0, 18.5),
+                    (1, 17.5),
+                    (2, 17.0),
+                    (3, 17.5),
+                    (4, 17.9),
+                    (5, 18.0),
+                    (6, 17.5)
+                ]
+            ),
+            [false, false, true, true, true, false, false]
+        );
+    }
+
+    #[test]
+    fn min_cycle_time() {
+        let mut heater = heater(Seconds(60), None);
+        assert_eq!(
+            run(
+                &mut heater,
+                &[
+                    (0, 16.0),
+                    (30, 19.0),
+                    (59, 19.0),
+                    (60, 19.0),
+                    (90, 16.0),
+                    (120, 16.0)
+                ]
+            ),
+            [true, true, true, false, false, true]
+        );
+    }
+
+    #[test]
+    fn max_on_time_locks_out_until_no_longer_demanded() {
+        let mut heater = heater(Seconds(0), Some(Seconds(100)));
+        assert_eq!(
+            run(
+                &mut heater,
+                &[
+                    (0, 16.0),
+                    (99, 16.0),
+                    (100, 16.0),
+                    (150, 16.0),
+                    (200, 19.0),
+                    (201, 16.0)
+                ]
+            ),
+            [true, true, false, false, false, true]
+        );
+    }
+
+    #[test]
+    fn max_on_time_is_checked_without_measurements() {
+        let mut heater = heater(Seconds(0), Some(Seconds(100)));
+        run(&mut heater, &[(0, 16.0)]);
+        assert_eq!(heater.check_max_on_time(&at(99)), None);
+        assert_eq!(
+            heater.check_max_on_time(&at(100)),
+            Some(Actuation {
+                on: false,
+                reason: Reason::MaxOnTime
+            })
+        );
+        assert!(!heater.is_on());
+    }
+
+    #[test]
+    fn sensor_failure_shutdown() {
+        let mut heater = heater(Seconds(300), None);
+        run(&mut heater, &[(0, 16.0)]);
+        // Ignores the minimum cycle time
+        assert_eq!(
+            heater.shutdown(&at(1)),
+            Some(Actuation {
+                on: false,
+                reason: Reason::SensorFailure
+            })
+        );
+        assert!(!heater.is_on());
+        // Already off
+        assert_eq!(heater.shutdown(&at(2)), None);
+    }
+
+    #[test]
+    fn loop_waits_for_the_warm_up() {
+        let mut heater = heater(Seconds(0), None);
+        let rules = RulesEngine::new(&[]);
+        let warm_up = WarmUp::new(
+            WarmUpConfig {
+                temperature: Seconds(60),
+                ..SETTLED
+            },
+            &at(0),
+        );
+        heater.update(&at(30), &measurement(16.0), &rules, &warm_up);
+        assert!(!heater.is_on());
+        heater.update(&at(60), &measurement(16.0), &rules, &warm_up);
+        assert!(heater.is_on());
+    }
+
+    #[test]
+    fn relay_off_state() {
+        assert_eq!(super::relay_off_state(false), PinState::Low);
+        assert_eq!(super::relay_off_state(true), PinState::High);
+    }
+}
@@ -1,15 +1,574 @@
-use crate::hal::hal::PwmPin;
-use crate::util;
-use bme680::FieldData;
+use crate::barometer;
+use crate::buzzer::{Buzzer, Pattern};
+use crate::config;
+use crate::psychro::{self, Psychrometrics};
+use crate::rate::{self, RateHistory};
+use crate::sensor::Measurement;
+use crate::system_clock::SystemClock;
+use crate::warm_up::{Quantity, WarmUp};
 use core::fmt;
-use embedded_time::duration::Minutes;
+use embedded_hal::PwmPin;
+use embedded_time::{
+    duration::{Minutes, Seconds},
+    Instant,
+};
+use heapless::Vec;
+
+/// Rules beyond this are ignored
+pub const MAX_RULES: usize = 16;
+
+/// Events queued between calls to Alarm::take_events
+pub const MAX_PENDING_EVENTS: usize = 2 * MAX_RULES;
+
+/// Distinct channels with rate rules, each keeps a few hours of history
+pub const MAX_RATE_CHANNELS: usize = 4;
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Severity {
+    /// Display only
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 3] = [Severity::Info, Severity::Warning, Severity::Critical];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Info => f.write_str("I"),
+            Severity::Warning => f.write_str("W"),
+            Severity::Critical => f.write_str("C"),
+        }
+    }
+}
+
+/// Values are in the internal units, °C, % and hPa
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Channel {
+    /// Degree celsius (°C)
+    Temperature,
+    /// % relative humidity
+    Humidity,
+    /// Station pressure in hectopascal (hPa)
+    Pressure,
+    /// Sea-level pressure in hectopascal (hPa), see config::STATION_ALTITUDE_M
+    SeaLevelPressure,
+    /// Gas resistance in ohms (Ω), lower means more VOCs
+    GasResistance,
+    /// Dew point in degree celsius (°C)
+    DewPoint,
+    /// Temperature minus dew point in degree celsius (°C), condensation risk
+    /// as it approaches zero
+    DewPointSpread,
+    /// Absolute humidity in g/m³
+    AbsoluteHumidity,
+    /// Heat index in degree celsius (°C)
+    HeatIndex,
+    /// Humidex
+    Humidex,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 10] = [
+        Channel::Temperature,
+        Channel::Humidity,
+        Channel::Pressure,
+        Channel::SeaLevelPressure,
+        Channel::GasResistance,
+        Channel::DewPoint,
+        Channel::DewPointSpread,
+        Channel::AbsoluteHumidity,
+        Channel::HeatIndex,
+        Channel::Humidex,
+    ];
+
+    /// None if the value isn't available, e.g. the gas heater isn't stable
+    pub fn value(self, data: &Measurement) -> Option<f32> {
+        let derived = || Psychrometrics::from(data);
+        match self {
+            Channel::Temperature => Some(data.temperature),
+            Channel::Humidity => Some(data.humidity),
+            Channel::Pressure => Some(data.pressure),
+            Channel::SeaLevelPressure => Some(barometer::sea_level_pressure(
+                data.pressure,
+                config::STATION_ALTITUDE_M,
+                data.temperature,
+            )),
+            Channel::GasResistance => data.gas_resistance.map(|r| r as f32),
+            Channel::DewPoint => Some(psychro::dew_point(data.temperature, data.humidity)),
+            Channel::DewPointSpread => {
+                Some(data.temperature - psychro::dew_point(data.temperature, data.humidity))
+            }
+            Channel::AbsoluteHumidity => Some(derived().absolute_humidity),
+            Channel::HeatIndex => Some(derived().heat_index),
+            Channel::Humidex => Some(derived().humidex),
+        }
+    }
+
+    /// The sensor quantities the value is derived from
+    pub fn quantities(self) -> &'static [Quantity] {
+        const T: Quantity = Quantity::Temperature;
+        const H: Quantity = Quantity::Humidity;
+        const P: Quantity = Quantity::Pressure;
+        match self {
+            Channel::Temperature => &[T],
+            Channel::Humidity => &[H],
+            Channel::Pressure => &[P],
+            Channel::SeaLevelPressure => &[P, T],
+            Channel::GasResistance => &[Quantity::GasResistance],
+            Channel::DewPoint
+            | Channel::DewPointSpread
+            | Channel::AbsoluteHumidity
+            | Channel::HeatIndex
+            | Channel::Humidex => &[T, H],
+        }
+    }
+
+    /// Short label for the display
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Channel::Temperature => "T",
+            Channel::Humidity => "RH",
+            Channel::Pressure => "P",
+            Channel::SeaLevelPressure => "SLP",
+            Channel::GasResistance => "GAS",
+            Channel::DewPoint => "DP",
+            Channel::DewPointSpread => "DPS",
+            Channel::AbsoluteHumidity => "AH",
+            Channel::HeatIndex => "HI",
+            Channel::Humidex => "HX",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Channel::Temperature => "temperature",
+            Channel::Humidity => "humidity",
+            Channel::Pressure => "pressure",
+            Channel::SeaLevelPressure => "sea_level_pressure",
+            Channel::GasResistance => "gas_resistance",
+            Channel::DewPoint => "dew_point",
+            Channel::DewPointSpread => "dew_point_spread",
+            Channel::AbsoluteHumidity => "absolute_humidity",
+            Channel::HeatIndex => "heat_index",
+            Channel::Humidex => "humidex",
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub enum Comparison {
+    Above(f32),
+    Below(f32),
+    /// Outside of the inclusive band [low, high]
+    Outside {
+        low: f32,
+        high: f32,
+    },
+}
+
+impl Comparison {
+    pub fn is_met(&self, value: f32) -> bool {
+        match *self {
+            Comparison::Above(threshold) => value > threshold,
+            Comparison::Below(threshold) => value < threshold,
+            Comparison::Outside { low, high } => !(low..=high).contains(&value),
+        }
+    }
+
+    /// How far the value is past the threshold, negative while not met
+    pub fn excess(&self, value: f32) -> f32 {
+        match *self {
+            Comparison::Above(threshold) => value - threshold,
+            Comparison::Below(threshold) => threshold - value,
+            Comparison::Outside { low, high } => (low - value).max(value - high),
+        }
+    }
+
+    /// Whether the value has moved back past the threshold by at least
+    /// the hysteresis
+    pub fn is_cleared(&self, value: f32, hysteresis: f32) -> bool {
+        match *self {
+            Comparison::Above(threshold) => value <= threshold - hysteresis,
+            Comparison::Below(threshold) => value >= threshold + hysteresis,
+            Comparison::Outside { low, high } => {
+                ((low + hysteresis)..=(high - hysteresis)).contains(&value)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Comparison::Above(threshold) => write!(f, "above {}", threshold),
+            Comparison::Below(threshold) => write!(f, "below {}", threshold),
+            Comparison::Outside { low, high } => write!(f, "outside {} {}", low, high),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub struct AlarmRule {
+    pub channel: Channel,
+    pub comparison: Comparison,
+    pub severity: Severity,
+    /// Band past the threshold the value must return through before the
+    /// rule clears, in the channel's units
+    pub hysteresis: f32,
+    /// The condition must hold this long before the rule triggers
+    pub trigger_delay: Seconds,
+    /// Once triggered the rule stays active at least this long
+    pub min_on_time: Seconds,
+    /// Compare the change over this window instead of the value, in the
+    /// channel's units per window
+    pub rate_window: Option<Minutes>,
+}
+
+impl AlarmRule {
+    /// Parse the Display form, `<channel> <above|below> <threshold> <severity>`
+    /// or `<channel> outside <low> <high> <severity>`, followed by the
+    /// optional `hysteresis <h>`, `delay <sec>`, `min_on <sec>` and
+    /// `rate <min>` fields
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut fields = s.split_whitespace();
+        let channel = fields.next()?;
+        let channel = *Channel::ALL.iter().find(|c| c.name() == channel)?;
+        let kind = fields.next()?;
+        let mut threshold = || fields.next().and_then(|f| f.parse::<f32>().ok());
+        let comparison = match kind {
+            "above" => Comparison::Above(threshold()?),
+            "below" => Comparison::Below(threshold()?),
+            "outside" => Comparison::Outside {
+                low: threshold()?,
+                high: threshold()?,
+            },
+            _ => return None,
+        };
+        let severity = fields.next()?;
+        let severity = *Severity::ALL.iter().find(|s| s.name() == severity)?;
+        let mut rule = AlarmRule {
+            channel,
+            comparison,
+            severity,
+            hysteresis: 0.0,
+            trigger_delay: config::ALARM_TRIGGER_DELAY,
+            min_on_time: config::ALARM_MIN_ON_TIME,
+            rate_window: None,
+        };
+        while let Some(key) = fields.next() {
+            let value = fields.next()?;
+            match key {
+                "hysteresis" => rule.hysteresis = value.parse::<f32>().ok()?,
+                "delay" => rule.trigger_delay = Seconds(value.parse::<u32>().ok()?),
+                "min_on" => rule.min_on_time = Seconds(value.parse::<u32>().ok()?),
+                "rate" => {
+                    let window = value.parse::<u32>().ok()?;
+                    if !(1..=rate::MAX_WINDOW.0).contains(&window) {
+                        return None;
+                    }
+                    rule.rate_window.replace(Minutes(window));
+                }
+                _ => return None,
+            }
+        }
+        if rule.can_clear() {
+            Some(rule)
+        } else {
+            None
+        }
+    }
+
+    /// An outside band narrower than twice the hysteresis has no value the
+    /// rule would clear at once triggered
+    pub fn can_clear(&self) -> bool {
+        match self.comparison {
+            Comparison::Outside { low, high } => (2.0 * self.hysteresis) < (high - low),
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for AlarmRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} hysteresis {} delay {} min_on {}",
+            self.channel.name(),
+            self.comparison,
+            self.severity.name(),
+            self.hysteresis,
+            self.trigger_delay.0,
+            self.min_on_time.0
+        )?;
+        if let Some(window) = self.rate_window {
+            write!(f, " rate {}", window.0)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum EventKind {
+    Raised,
+    Acknowledged,
+    Cleared,
+}
+
+impl EventKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            EventKind::Raised => "raised",
+            EventKind::Acknowledged => "acknowledged",
+            EventKind::Cleared => "cleared",
+        }
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventKind::Raised => f.write_str("ON"),
+            EventKind::Acknowledged => f.write_str("ACK"),
+            EventKind::Cleared => f.write_str("OFF"),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub struct AlarmEvent {
+    pub kind: EventKind,
+    pub severity: Severity,
+    /// The rule raised or cleared, None for an acknowledgement
+    pub rule: Option<AlarmRule>,
+    /// Value when raised, the value furthest past the threshold while active
+    /// when cleared
+    pub value: Option<f32>,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum RuleState {
+    Clear,
+    /// Condition met since, waiting out the trigger delay
+    Pending(Instant<SystemClock>),
+    /// Triggered at, and the value furthest past the threshold since
+    Active(Instant<SystemClock>, f32),
+}
+
+impl RuleState {
+    fn is_active(&self) -> bool {
+        matches!(self, RuleState::Active(_, _))
+    }
+}
+
+fn elapsed(now: &Instant<SystemClock>, since: &Instant<SystemClock>, duration: Seconds) -> bool {
+    match now.checked_duration_since(since) {
+        Some(dur) => dur >= duration.into(),
+        None => false,
+    }
+}
+
+/// Evaluates the alarm rules, independent of the buzzer hardware
+pub struct RulesEngine {
+    rules: Vec<AlarmRule, MAX_RULES>,
+    states: [RuleState; MAX_RULES],
+    /// History of the channels with rate rules
+    histories: Vec<(Channel, RateHistory), MAX_RATE_CHANNELS>,
+}
+
+impl RulesEngine {
+    /// Rules beyond MAX_RULES are ignored, as are rate rules on channels
+    /// beyond MAX_RATE_CHANNELS (see untracked_rules)
+    pub fn new(rules: &[AlarmRule]) -> Self {
+        let mut engine = RulesEngine {
+            rules: Vec::new(),
+            states: [RuleState::Clear; MAX_RULES],
+            histories: Vec::new(),
+        };
+        engine.set_rules(rules);
+        engine
+    }
+
+    /// Replace the rules, all start out clear. The histories of channels
+    /// that still have rate rules are kept.
+    pub fn set_rules(&mut self, rules: &[AlarmRule]) {
+        self.rules = rules.iter().take(MAX_RULES).copied().collect();
+        self.states = [RuleState::Clear; MAX_RULES];
+        let old_histories = &mut self.histories;
+        let mut histories: Vec<(Channel, RateHistory), MAX_RATE_CHANNELS> = Vec::new();
+        for rule in self.rules.iter().filter(|rule| rule.rate_window.is_some()) {
+            if histories
+                .iter()
+                .any(|(channel, _)| *channel == rule.channel)
+            {
+                continue;
+            }
+            let history = old_histories
+                .iter()
+                .position(|(channel, _)| *channel == rule.channel)
+                .map(|i| old_histories.swap_remove(i).1)
+                .unwrap_or_default();
+            let _ = histories.push((rule.channel, history));
+        }
+        self.histories = histories;
+    }
+
+    pub fn rules(&self) -> &[AlarmRule] {
+        &self.rules
+    }
+
+    /// Rate rules on channels beyond MAX_RATE_CHANNELS, they never trigger
+    pub fn untracked_rules(&self) -> impl Iterator<Item = &AlarmRule> + '_ {
+        self.rules.iter().filter(move |rule| {
+            rule.rate_window.is_some()
+                && !self
+                    .histories
+                    .iter()
+                    .any(|(channel, _)| *channel == rule.channel)
+        })
+    }
+
+    /// Sample the channels with rate rules, including while they warm up
+    /// and while the rules aren't checked, so a rate is available as soon
+    /// as they are
+    pub fn update_histories(&mut self, now: &Instant<SystemClock>, data: &Measurement) {
+        for (channel, history) in self.histories.iter_mut() {
+            if let Some(value) = channel.value(data) {
+                history.update(now, value);
+            }
+        }
+    }
+
+    /// Rules that trigger or clear are reported to on_event, rules on
+    /// channels still warming up are skipped
+    pub fn update<F>(
+        &mut self,
+        now: &Instant<SystemClock>,
+        data: &Measurement,
+        warm_up: &WarmUp,
+        mut on_event: F,
+    ) -> Option<Severity>
+    where
+        F: FnMut(AlarmEvent),
+    {
+        self.update_histories(now, data);
+        for (rule, state) in self.rules.iter().zip(self.states.iter_mut()) {
+            if !warm_up.are_settled(rule.channel.quantities(), now) {
+                continue;
+            }
+            let value = match rule.rate_window {
+                None => rule.channel.value(data),
+                Some(window) => self
+                    .histories
+                    .iter()
+                    .find(|(channel, _)| *channel == rule.channel)
+                    .and_then(|(_, history)| history.rate(window)),
+            };
+            // A channel without a value keeps its previous state
+            let value = match value {
+                Some(value) => value,
+                None => continue,
+            };
+            let met = rule.comparison.is_met(value);
+            let triggered = match *state {
+                RuleState::Clear if met && rule.trigger_delay == Seconds(0) => true,
+                RuleState::Clear if met => {
+                    *state = RuleState::Pending(*now);
+                    false
+                }
+                RuleState::Clear => false,
+                RuleState::Pending(_) if !met => {
+                    *state = RuleState::Clear;
+                    false
+                }
+                RuleState::Pending(since) => elapsed(now, &since, rule.trigger_delay),
+                RuleState::Active(since, peak) => {
+                    let peak = if rule.comparison.excess(value) > rule.comparison.excess(peak) {
+                        value
+                    } else {
+                        peak
+                    };
+                    if rule.comparison.is_cleared(value, rule.hysteresis)
+                        && elapsed(now, &since, rule.min_on_time)
+                    {
+                        *state = RuleState::Clear;
+                        on_event(AlarmEvent {
+                            kind: EventKind::Cleared,
+                            severity: rule.severity,
+                            rule: Some(*rule),
+                            value: Some(peak),
+                        });
+                    } else {
+                        *state = RuleState::Active(since, peak);
+                    }
+                    false
+                }
+            };
+            if triggered {
+                *state = RuleState::Active(*now, value);
+                on_event(AlarmEvent {
+                    kind: EventKind::Raised,
+                    severity: rule.severity,
+                    rule: Some(*rule),
+                    value: Some(value),
+                });
+            }
+        }
+        self.severity()
+    }
+
+    /// Clear all rules, the active ones are reported to on_event as cleared
+    pub fn clear<F>(&mut self, mut on_event: F)
+    where
+        F: FnMut(AlarmEvent),
+    {
+        for (rule, state) in self.rules.iter().zip(self.states.iter_mut()) {
+            if let RuleState::Active(_, peak) = *state {
+                on_event(AlarmEvent {
+                    kind: EventKind::Cleared,
+                    severity: rule.severity,
+                    rule: Some(*rule),
+                    value: Some(peak),
+                });
+            }
+            *state = RuleState::Clear;
+        }
+    }
+
+    /// Highest severity of the active rules
+    pub fn severity(&self) -> Option<Severity> {
+        self.active_rules().map(|rule| rule.severity).max()
+    }
+
+    pub fn active_rules(&self) -> impl Iterator<Item = &AlarmRule> + '_ {
+        self.rules
+            .iter()
+            .zip(self.states.iter())
+            .filter(|(_rule, state)| state.is_active())
+            .map(|(rule, _state)| rule)
+    }
+}
 
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 pub enum AlarmStatus {
     /// Alarm is not activing monitoring
     NotMonitoring,
-    /// Alarm is on
-    On,
+    /// Alarm is on, highest active severity
+    On(Severity),
+    /// Alarm was acknowledged and is silenced, highest severity latched
+    /// since the acknowledgement
+    Snoozed(Severity),
     /// Alarm is off
     Off,
 }
@@ -24,39 +583,36 @@ impl fmt::Display for AlarmStatus {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             AlarmStatus::NotMonitoring => f.write_str("X"),
-            AlarmStatus::On => f.write_str("Y"),
+            AlarmStatus::On(severity) => write!(f, "{}", severity),
+            AlarmStatus::Snoozed(severity) => write!(f, "{}Z", severity),
             AlarmStatus::Off => f.write_str("N"),
         }
     }
 }
 
 pub struct Alarm<PWM> {
-    pwm: PWM,
+    buzzer: Buzzer<PWM>,
+    rules: RulesEngine,
     monitoring: bool,
-    on: bool,
-}
-
-impl<Whatev> Alarm<Whatev> {
-    /// Wait this much time before monitoring the alarm (if monitoring enabled)
-    pub const WARM_UP_DELAY: Minutes = Minutes(10);
+    /// Acknowledged at, and the latched severity
+    snoozed: Option<(Instant<SystemClock>, Severity)>,
+    quiet_hours: bool,
+    /// Not yet taken by take_events
+    events: Vec<AlarmEvent, MAX_PENDING_EVENTS>,
 }
 
 impl<PWM> Alarm<PWM>
 where
     PWM: PwmPin<Duty = u16>,
 {
-    // [68, 72] °F ([20, 22.2] °C) is ideal
-    pub const TEMP_F_MIN: f32 = 66.0;
-    pub const TEMP_F_MAX: f32 = 74.0;
-
-    pub fn new(mut pwm: PWM) -> Self {
-        let max_duty = pwm.get_max_duty();
-        pwm.set_duty(max_duty / 2);
-        pwm.disable();
+    pub fn new(pwm: PWM) -> Self {
         Alarm {
-            pwm,
+            buzzer: Buzzer::new(pwm),
+            rules: RulesEngine::new(config::ALARM_RULES),
             monitoring: true,
-            on: false,
+            snoozed: None,
+            quiet_hours: false,
+            events: Vec::new(),
         }
     }
 
@@ -65,41 +621,765 @@ where
     }
 
     pub fn set_monitoring(&mut self, monitoring: bool) {
-        self.set_on_off(false);
+        self.buzzer.set_alarm(None);
+        self.clear_rules();
+        self.snoozed = None;
         self.monitoring = monitoring;
     }
 
-    pub fn set_on_off(&mut self, on: bool) {
-        if on {
-            self.pwm.enable();
+    /// Silence a sounding (or already snoozed) alarm for config::ALARM_SNOOZE,
+    /// returns false if there was nothing to acknowledge
+    pub fn acknowledge(&mut self, now: &Instant<SystemClock>) -> bool {
+        let severity = match self.status() {
+            AlarmStatus::On(severity) if Pattern::for_severity(severity).is_some() => severity,
+            AlarmStatus::Snoozed(severity) => severity,
+            _ => return false,
+        };
+        self.snoozed.replace((*now, severity));
+        self.buzzer.set_alarm(None);
+        let _ = self.events.push(AlarmEvent {
+            kind: EventKind::Acknowledged,
+            severity,
+            rule: None,
+            value: None,
+        });
+        true
+    }
+
+    /// Events since the last call, to be timestamped and logged
+    pub fn take_events(&mut self) -> Vec<AlarmEvent, MAX_PENDING_EVENTS> {
+        core::mem::take(&mut self.events)
+    }
+
+    /// During quiet hours only severities of at least
+    /// config::QUIET_HOURS_MIN_SEVERITY sound
+    pub fn set_quiet_hours(&mut self, quiet_hours: bool) {
+        if quiet_hours != self.quiet_hours {
+            self.quiet_hours = quiet_hours;
+            if self.monitoring && self.snoozed.is_none() {
+                self.buzzer.set_alarm(self.pattern());
+            }
+        }
+    }
+
+    /// An alarm that would sound is muted by quiet hours
+    pub fn visual_only(&self) -> bool {
+        match self.status() {
+            AlarmStatus::On(severity) => {
+                Pattern::for_severity(severity).is_some() && self.pattern().is_none()
+            }
+            _ => false,
+        }
+    }
+
+    /// Buzzer pattern for the active rules, ignoring snooze
+    fn pattern(&self) -> Option<Pattern> {
+        let severity = self.rules.severity()?;
+        if self.quiet_hours && severity < config::QUIET_HOURS_MIN_SEVERITY {
+            None
         } else {
-            self.pwm.disable();
+            Pattern::for_severity(severity)
         }
-        self.on = on;
+    }
+
+    /// Play a one-shot pattern, e.g. to acknowledge a button press
+    pub fn play(&mut self, pattern: Pattern) {
+        self.buzzer.play(pattern);
+    }
+
+    /// Advance the buzzer pattern, call on every SysTick tick
+    pub fn update_buzzer(&mut self, ticks: u32) {
+        self.buzzer.update(ticks);
     }
 
     pub fn status(&self) -> AlarmStatus {
         if self.monitoring {
-            if self.on {
-                AlarmStatus::On
-            } else {
-                AlarmStatus::Off
+            match (self.snoozed, self.rules.severity()) {
+                (Some((_at, latched)), severity) => {
+                    AlarmStatus::Snoozed(severity.map_or(latched, |s| s.max(latched)))
+                }
+                (None, Some(severity)) => AlarmStatus::On(severity),
+                (None, None) => AlarmStatus::Off,
             }
         } else {
             AlarmStatus::NotMonitoring
         }
     }
 
-    pub fn check_temperature(&mut self, data: &FieldData) {
+    pub fn rules(&self) -> &RulesEngine {
+        &self.rules
+    }
+
+    pub fn set_rules(&mut self, rules: &[AlarmRule]) {
+        self.buzzer.set_alarm(None);
+        self.snoozed = None;
+        self.clear_rules();
+        self.rules.set_rules(rules);
+    }
+
+    /// Rules aren't checked while the sensor is failed, so the active ones
+    /// are cleared rather than left sounding on stale readings
+    pub fn sensor_failed(&mut self) {
+        self.buzzer.set_alarm(None);
+        self.snoozed = None;
+        self.clear_rules();
+    }
+
+    /// The active rules are logged as cleared
+    fn clear_rules(&mut self) {
+        let events = &mut self.events;
+        self.rules.clear(|event| {
+            // Dropped if not taken in time
+            let _ = events.push(event);
+        });
+    }
+
+    pub fn check(&mut self, now: &Instant<SystemClock>, data: &Measurement, warm_up: &WarmUp) {
         if self.monitoring {
-            let temp_f = util::celsius_to_fahrenheit(data.temperature_celsius());
-            if !(Self::TEMP_F_MIN..=Self::TEMP_F_MAX).contains(&temp_f) {
-                self.set_on_off(true);
-            } else {
-                self.set_on_off(false)
+            let events = &mut self.events;
+            let severity = self.rules.update(now, data, warm_up, |event| {
+                // Dropped if not taken in time
+                let _ = events.push(event);
+            });
+            if let Some((at, latched)) = self.snoozed {
+                // Snooze ends when it expires or a more severe rule triggers
+                if elapsed(now, &at, config::ALARM_SNOOZE) || severity > Some(latched) {
+                    self.snoozed = None;
+                }
+            }
+            if self.snoozed.is_none() {
+                self.buzzer.set_alarm(self.pattern());
             }
         } else {
-            self.set_on_off(false)
+            self.rules.update_histories(now, data);
+            self.buzzer.set_alarm(None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system_clock::TICK_HZ;
+    use crate::warm_up::WarmUpConfig;
+    use core::cell::Cell;
+    use core::fmt::Write;
+    use heapless::String;
+
+    const SETTLED: WarmUpConfig = WarmUpConfig {
+        temperature: Seconds(0),
+        humidity: Seconds(0),
+        pressure: Seconds(0),
+        gas_resistance: Seconds(0),
+    };
+
+    fn at(sec: u32) -> Instant<SystemClock> {
+        Instant::new(sec)
+    }
+
+    fn measurement(temperature: f32) -> Measurement {
+        Measurement {
+            temperature,
+            humidity: 50.0,
+            pressure: 1013.25,
+            gas_resistance: None,
+        }
+    }
+
+    fn rule(comparison: Comparison, severity: Severity) -> AlarmRule {
+        AlarmRule {
+            channel: Channel::Temperature,
+            comparison,
+            severity,
+            hysteresis: 0.0,
+            trigger_delay: Seconds(0),
+            min_on_time: Seconds(0),
+            rate_window: None,
+        }
+    }
+
+    /// Feeds the temperatures a second apart, returns the kinds of the
+    /// events and the severity after each
+    fn run(
+        engine: &mut RulesEngine,
+        temperatures: &[f32],
+    ) -> Vec<(Option<EventKind>, Option<Severity>), 16> {
+        let warm_up = WarmUp::new(SETTLED, &at(0));
+        let mut out = Vec::new();
+        for (sec, t) in temperatures.iter().enumerate() {
+            let mut kind = None;
+            let severity = engine.update(&at(sec as u32), &measurement(*t), &warm_up, |event| {
+                kind.replace(event.kind);
+            });
+            out.push((kind, severity)).unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn above() {
+        let mut engine = RulesEngine::new(&[rule(Comparison::Above(30.0), Severity::Warning)]);
+        let w = Some(Severity::Warning);
+        assert_eq!(
+            run(&mut engine, &[29.0, 30.0, 31.0, 32.0, 30.0]),
+            [
+                (None, None),
+                (None, None),
+                (Some(EventKind::Raised), w),
+                (None, w),
+                (Some(EventKind::Cleared), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn below() {
+        let mut engine = RulesEngine::new(&[rule(Comparison::Below(10.0), Severity::Warning)]);
+        let w = Some(Severity::Warning);
+        assert_eq!(
+            run(&mut engine, &[11.0, 10.0, 9.0, 12.0]),
+            [
+                (None, None),
+                (None, None),
+                (Some(EventKind::Raised), w),
+                (Some(EventKind::Cleared), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn outside() {
+        let comparison = Comparison::Outside {
+            low: 10.0,
+            high: 20.0,
+        };
+        let mut engine = RulesEngine::new(&[rule(comparison, Severity::Critical)]);
+        let c = Some(Severity::Critical);
+        assert_eq!(
+            run(&mut engine, &[15.0, 10.0, 20.0, 21.0, 9.0, 15.0, 5.0]),
+            [
+                (None, None),
+                (None, None),
+                (None, None),
+                (Some(EventKind::Raised), c),
+                (None, c),
+                (Some(EventKind::Cleared), None),
+                (Some(EventKind::Raised), c),
+            ]
+        );
+    }
+
+    #[test]
+    fn condition_shorter_than_the_trigger_delay_never_fires() {
+        let mut engine = RulesEngine::new(&[AlarmRule {
+            trigger_delay: Seconds(3),
+            ..rule(Comparison::Above(30.0), Severity::Warning)
+        }]);
+        let w = Some(Severity::Warning);
+        assert_eq!(
+            run(
+                &mut engine,
+                &[31.0, 35.0, 31.0, 29.0, 31.0, 31.0, 29.0, 31.0, 31.0, 31.0, 31.0, 31.0]
+            ),
+            [
+                (None, None),
+                (None, None),
+                (None, None),
+                (None, None),
+                (None, None),
+                (None, None),
+                (None, None),
+                (None, None),
+                (None, None),
+                (None, None),
+                (Some(EventKind::Raised), w),
+                (None, w),
+            ]
+        );
+    }
+
+    #[test]
+    fn active_rule_is_held_for_the_min_on_time() {
+        let mut engine = RulesEngine::new(&[AlarmRule {
+            min_on_time: Seconds(5),
+            ..rule(Comparison::Above(30.0), Severity::Warning)
+        }]);
+        let w = Some(Severity::Warning);
+        assert_eq!(
+            run(&mut engine, &[31.0, 29.0, 29.0, 29.0, 29.0, 29.0, 29.0]),
+            [
+                (Some(EventKind::Raised), w),
+                (None, w),
+                (None, w),
+                (None, w),
+                (None, w),
+                (Some(EventKind::Cleared), None),
+                (None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn min_on_time_waits_for_the_condition_to_clear() {
+        let mut engine = RulesEngine::new(&[AlarmRule {
+            min_on_time: Seconds(2),
+            ..rule(Comparison::Above(30.0), Severity::Warning)
+        }]);
+        let w = Some(Severity::Warning);
+        assert_eq!(
+            run(&mut engine, &[31.0, 29.0, 31.0, 31.0, 29.0]),
+            [
+                (Some(EventKind::Raised), w),
+                (None, w),
+                (None, w),
+                (None, w),
+                (Some(EventKind::Cleared), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn cleared_event_reports_the_peak() {
+        let mut engine = RulesEngine::new(&[rule(Comparison::Above(30.0), Severity::Warning)]);
+        let warm_up = WarmUp::new(SETTLED, &at(0));
+        let mut values: Vec<(EventKind, Option<f32>), 4> = Vec::new();
+        for (sec, t) in [31.0, 35.0, 33.0, 29.0].iter().enumerate() {
+            engine.update(&at(sec as u32), &measurement(*t), &warm_up, |event| {
+                values.push((event.kind, event.value)).unwrap()
+            });
+        }
+        assert_eq!(
+            values,
+            [
+                (EventKind::Raised, Some(31.0)),
+                (EventKind::Cleared, Some(35.0))
+            ]
+        );
+    }
+
+    #[test]
+    fn highest_severity_of_the_active_rules() {
+        let mut engine = RulesEngine::new(&[
+            rule(Comparison::Above(25.0), Severity::Info),
+            rule(Comparison::Above(30.0), Severity::Critical),
+            rule(Comparison::Above(28.0), Severity::Warning),
+        ]);
+        let severities: Vec<Option<Severity>, 16> =
+            run(&mut engine, &[20.0, 26.0, 29.0, 31.0, 29.0])
+                .iter()
+                .map(|(_, severity)| *severity)
+                .collect();
+        assert_eq!(
+            severities,
+            [
+                None,
+                Some(Severity::Info),
+                Some(Severity::Warning),
+                Some(Severity::Critical),
+                Some(Severity::Warning),
+            ]
+        );
+        assert_eq!(engine.active_rules().count(), 2);
+    }
+
+    #[test]
+    fn parse_threshold_rules() {
+        assert_eq!(
+            AlarmRule::parse("temperature above 30 warning"),
+            Some(AlarmRule {
+                trigger_delay: config::ALARM_TRIGGER_DELAY,
+                min_on_time: config::ALARM_MIN_ON_TIME,
+                ..rule(Comparison::Above(30.0), Severity::Warning)
+            })
+        );
+        assert_eq!(
+            AlarmRule::parse("humidity below -1.5 info").map(|r| (r.channel, r.comparison)),
+            Some((Channel::Humidity, Comparison::Below(-1.5)))
+        );
+        assert_eq!(
+            AlarmRule::parse("  dew_point_spread outside 2 40 critical ").map(|r| r.comparison),
+            Some(Comparison::Outside {
+                low: 2.0,
+                high: 40.0
+            })
+        );
+    }
+
+    #[test]
+    fn parse_optional_fields() {
+        let rule =
+            AlarmRule::parse("pressure below 1000 warning hysteresis 2 delay 10 min_on 60 rate 30")
+                .unwrap();
+        assert_eq!(rule.hysteresis, 2.0);
+        assert_eq!(rule.trigger_delay, Seconds(10));
+        assert_eq!(rule.min_on_time, Seconds(60));
+        assert_eq!(rule.rate_window, Some(Minutes(30)));
+    }
+
+    #[test]
+    fn parse_rejects_malformed_rules() {
+        for s in [
+            "",
+            "temperature",
+            "temp above 30 warning",
+            "temperature over 30 warning",
+            "temperature above warm warning",
+            "temperature above 30",
+            "temperature above 30 loud",
+            "temperature outside 10 warning",
+            "temperature above 30 warning hysteresis",
+            "temperature above 30 warning delay -1",
+            "temperature above 30 warning color red",
+            "temperature above 30 warning rate 0",
+            "temperature above 30 warning rate 181",
+        ]
+        .iter()
+        {
+            assert_eq!(AlarmRule::parse(s), None, "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_an_outside_band_that_never_clears() {
+        assert_eq!(
+            AlarmRule::parse("temperature outside 20 22 warning hysteresis 1"),
+            None
+        );
+        assert_eq!(AlarmRule::parse("temperature outside 22 20 warning"), None);
+        assert!(AlarmRule::parse("temperature outside 20 22 warning hysteresis 0.9").is_some());
+        assert!(AlarmRule::parse("temperature above 20 warning hysteresis 5").is_some());
+    }
+
+    #[test]
+    fn default_rules_can_clear() {
+        assert!(config::ALARM_RULES.iter().all(AlarmRule::can_clear));
+    }
+
+    #[test]
+    fn display_parses_back() {
+        let rule = AlarmRule {
+            hysteresis: 0.5,
+            trigger_delay: Seconds(30),
+            min_on_time: Seconds(120),
+            rate_window: Some(Minutes(60)),
+            ..rule(
+                Comparison::Outside {
+                    low: 18.5,
+                    high: 26.0,
+                },
+                Severity::Critical,
+            )
+        };
+        let mut s: String<128> = String::new();
+        write!(&mut s, "{}", rule).unwrap();
+        assert_eq!(AlarmRule::parse(&s), Some(rule));
+    }
+
+    struct MockPwm<'a> {
+        enabled: &'a Cell<bool>,
+    }
+
+    impl<'a> PwmPin for MockPwm<'a> {
+        type Duty = u16;
+
+        fn disable(&mut self) {
+            self.enabled.set(false);
+        }
+
+        fn enable(&mut self) {
+            self.enabled.set(true);
+        }
+
+        fn get_duty(&self) -> u16 {
+            0
+        }
+
+        fn get_max_duty(&self) -> u16 {
+            u16::MAX
+        }
+
+        fn set_duty(&mut self, _duty: u16) {}
+    }
+
+    /// Feeds one temperature per second, returns whether the buzzer sounded
+    /// during each second. `before` is called ahead of each check.
+    fn sound<'a, F>(
+        alarm: &mut Alarm<MockPwm<'a>>,
+        buzzer: &Cell<bool>,
+        temperatures: &[f32],
+        mut before: F,
+    ) -> Vec<bool, 16>
+    where
+        F: FnMut(&mut Alarm<MockPwm<'a>>, u32),
+    {
+        let warm_up = WarmUp::new(SETTLED, &at(0));
+        let mut out = Vec::new();
+        for (sec, t) in temperatures.iter().enumerate() {
+            let sec = sec as u32;
+            before(alarm, sec);
+            alarm.check(&at(sec), &measurement(*t), &warm_up);
+            let mut sounded = false;
+            for tick in 0..TICK_HZ {
+                alarm.update_buzzer((sec * TICK_HZ) + tick);
+                sounded |= buzzer.get();
+            }
+            out.push(sounded).unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn warning_beeps_every_other_second_until_cleared() {
+        let buzzer = Cell::new(false);
+        let mut alarm = Alarm::new(MockPwm { enabled: &buzzer });
+        alarm.set_rules(&[rule(Comparison::Above(30.0), Severity::Warning)]);
+        assert_eq!(
+            sound(
+                &mut alarm,
+                &buzzer,
+                &[25.0, 31.0, 31.0, 31.0, 31.0, 29.0, 29.0],
+                |_, _| ()
+            ),
+            [false, true, false, true, false, false, false]
+        );
+    }
+
+    #[test]
+    fn critical_sounds_until_past_the_hysteresis() {
+        let buzzer = Cell::new(false);
+        let mut alarm = Alarm::new(MockPwm { enabled: &buzzer });
+        alarm.set_rules(&[AlarmRule {
+            hysteresis: 1.0,
+            ..rule(Comparison::Above(30.0), Severity::Critical)
+        }]);
+        assert_eq!(
+            sound(
+                &mut alarm,
+                &buzzer,
+                &[29.0, 31.0, 30.0, 29.5, 29.0, 29.5],
+                |_, _| ()
+            ),
+            [false, true, true, true, false, false]
+        );
+    }
+
+    #[test]
+    fn outside_band_clears_inside_the_hysteresis() {
+        let buzzer = Cell::new(false);
+        let mut alarm = Alarm::new(MockPwm { enabled: &buzzer });
+        alarm.set_rules(&[AlarmRule {
+            hysteresis: 0.5,
+            ..rule(
+                Comparison::Outside {
+                    low: 18.0,
+                    high: 24.0,
+                },
+                Severity::Critical,
+            )
+        }]);
+        assert_eq!(
+            sound(
+                &mut alarm,
+                &buzzer,
+                &[20.0, 17.0, 18.2, 18.5, 25.0, 23.8, 23.0],
+                |_, _| ()
+            ),
+            [false, true, true, false, true, true, false]
+        );
+    }
+
+    #[test]
+    fn acknowledge_silences_until_a_more_severe_rule_triggers() {
+        let buzzer = Cell::new(false);
+        let mut alarm = Alarm::new(MockPwm { enabled: &buzzer });
+        alarm.set_rules(&[
+            rule(Comparison::Above(30.0), Severity::Warning),
+            rule(Comparison::Above(35.0), Severity::Critical),
+        ]);
+        assert_eq!(
+            sound(
+                &mut alarm,
+                &buzzer,
+                &[31.0, 31.0, 31.0, 31.0, 36.0],
+                |alarm, sec| {
+                    if sec == 1 {
+                        assert!(alarm.acknowledge(&at(sec)));
+                    }
+                }
+            ),
+            [true, false, false, false, true]
+        );
+        assert_eq!(alarm.status(), AlarmStatus::On(Severity::Critical));
+    }
+
+    #[test]
+    fn not_monitoring_is_silent() {
+        let buzzer = Cell::new(false);
+        let mut alarm = Alarm::new(MockPwm { enabled: &buzzer });
+        alarm.set_rules(&[rule(Comparison::Above(30.0), Severity::Critical)]);
+        alarm.set_monitoring(false);
+        assert_eq!(
+            sound(&mut alarm, &buzzer, &[31.0, 31.0], |_, _| ()),
+            [false, false]
+        );
+        assert_eq!(alarm.status(), AlarmStatus::NotMonitoring);
+    }
+
+    #[test]
+    fn sensor_failure_clears_the_active_rules() {
+        let buzzer = Cell::new(false);
+        let mut alarm = Alarm::new(MockPwm { enabled: &buzzer });
+        alarm.set_rules(&[rule(Comparison::Above(30.0), Severity::Critical)]);
+        let warm_up = WarmUp::new(SETTLED, &at(0));
+        alarm.check(&at(0), &measurement(31.0), &warm_up);
+        assert_eq!(alarm.status(), AlarmStatus::On(Severity::Critical));
+        alarm.take_events();
+
+        alarm.sensor_failed();
+        assert_eq!(alarm.status(), AlarmStatus::Off);
+        let events = alarm.take_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, EventKind::Cleared);
+        for tick in 0..TICK_HZ {
+            alarm.update_buzzer(TICK_HZ + tick);
+            assert!(!buzzer.get());
+        }
+
+        // Raised again once readings resume
+        alarm.check(&at(60), &measurement(31.0), &warm_up);
+        assert_eq!(alarm.status(), AlarmStatus::On(Severity::Critical));
+    }
+
+    fn rate_rule(channel: Channel) -> AlarmRule {
+        AlarmRule {
+            channel,
+            rate_window: Some(Minutes(1)),
+            ..rule(Comparison::Above(1.0), Severity::Warning)
+        }
+    }
+
+    #[test]
+    fn single_sample_spike_does_not_trip_a_rate_rule() {
+        let mut engine = RulesEngine::new(&[rate_rule(Channel::Temperature)]);
+        let warm_up = WarmUp::new(SETTLED, &at(0));
+        for minute in 0..12 {
+            let temperature = if minute == 8 { 30.0 } else { 20.0 };
+            let severity = engine.update(
+                &at(minute * 60),
+                &measurement(temperature),
+                &warm_up,
+                |_| (),
+            );
+            assert_eq!(severity, None, "minute {}", minute);
+        }
+    }
+
+    #[test]
+    fn sustained_slope_trips_a_rate_rule() {
+        let mut engine = RulesEngine::new(&[rate_rule(Channel::Temperature)]);
+        let warm_up = WarmUp::new(SETTLED, &at(0));
+        let mut severities: Vec<Option<Severity>, 8> = Vec::new();
+        for minute in 0..8 {
+            let temperature = 20.0 + (2.0 * minute as f32);
+            let severity = engine.update(
+                &at(minute * 60),
+                &measurement(temperature),
+                &warm_up,
+                |_| (),
+            );
+            severities.push(severity).unwrap();
+        }
+        // Raised once the history covers the window and both medians
+        let w = Some(Severity::Warning);
+        assert_eq!(severities, [None, None, None, None, None, w, w, w]);
+    }
+
+    #[test]
+    fn rate_histories_survive_set_rules() {
+        let mut engine = RulesEngine::new(&[rate_rule(Channel::Temperature)]);
+        for minute in 0..6 {
+            engine.update_histories(&at(minute * 60), &measurement(20.0 + (2.0 * minute as f32)));
+        }
+        engine.set_rules(&[
+            rule(Comparison::Below(0.0), Severity::Info),
+            rate_rule(Channel::Temperature),
+        ]);
+        let warm_up = WarmUp::new(SETTLED, &at(0));
+        let severity = engine.update(&at(360), &measurement(32.0), &warm_up, |_| ());
+        assert_eq!(severity, Some(Severity::Warning));
+    }
+
+    #[test]
+    fn rate_histories_are_fed_while_not_monitoring() {
+        let buzzer = Cell::new(false);
+        let mut alarm = Alarm::new(MockPwm { enabled: &buzzer });
+        alarm.set_rules(&[rate_rule(Channel::Temperature)]);
+        alarm.set_monitoring(false);
+        let warm_up = WarmUp::new(SETTLED, &at(0));
+        for minute in 0..6 {
+            alarm.check(
+                &at(minute * 60),
+                &measurement(20.0 + (2.0 * minute as f32)),
+                &warm_up,
+            );
         }
+        alarm.set_monitoring(true);
+        alarm.check(&at(360), &measurement(32.0), &warm_up);
+        assert_eq!(alarm.status(), AlarmStatus::On(Severity::Warning));
+    }
+
+    #[test]
+    fn rate_rules_beyond_the_rate_channels_are_untracked() {
+        let engine = RulesEngine::new(&[
+            rate_rule(Channel::Temperature),
+            rate_rule(Channel::Humidity),
+            rate_rule(Channel::Temperature),
+            rate_rule(Channel::Pressure),
+            rate_rule(Channel::DewPoint),
+            rate_rule(Channel::HeatIndex),
+        ]);
+        let untracked: Vec<Channel, MAX_RULES> =
+            engine.untracked_rules().map(|rule| rule.channel).collect();
+        assert_eq!(untracked, [Channel::HeatIndex]);
+    }
+
+    fn event_kinds(alarm: &mut Alarm<MockPwm<'_>>) -> Vec<EventKind, MAX_PENDING_EVENTS> {
+        alarm.take_events().iter().map(|event| event.kind).collect()
+    }
+
+    #[test]
+    fn turning_monitoring_off_clears_the_active_rules() {
+        let buzzer = Cell::new(false);
+        let mut alarm = Alarm::new(MockPwm { enabled: &buzzer });
+        alarm.set_rules(&[
+            rule(Comparison::Above(30.0), Severity::Warning),
+            rule(Comparison::Above(40.0), Severity::Critical),
+        ]);
+        let warm_up = WarmUp::new(SETTLED, &at(0));
+        alarm.check(&at(0), &measurement(31.0), &warm_up);
+        assert_eq!(event_kinds(&mut alarm), [EventKind::Raised]);
+        alarm.set_monitoring(false);
+        let events = alarm.take_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, EventKind::Cleared);
+        assert_eq!(events[0].severity, Severity::Warning);
+        assert_eq!(events[0].value, Some(31.0));
+        alarm.set_monitoring(true);
+        assert!(alarm.take_events().is_empty());
+    }
+
+    #[test]
+    fn replacing_the_rules_clears_the_active_rules() {
+        let buzzer = Cell::new(false);
+        let mut alarm = Alarm::new(MockPwm { enabled: &buzzer });
+        alarm.set_rules(&[rule(Comparison::Above(30.0), Severity::Warning)]);
+        let warm_up = WarmUp::new(SETTLED, &at(0));
+        alarm.check(&at(0), &measurement(31.0), &warm_up);
+        alarm.set_rules(&[rule(Comparison::Above(35.0), Severity::Warning)]);
+        assert_eq!(
+            event_kinds(&mut alarm),
+            [EventKind::Raised, EventKind::Cleared]
+        );
+        assert_eq!(alarm.status(), AlarmStatus::Off);
     }
 }
@@ -0,0 +1,214 @@
+//! Timestamped history of alarm transitions, kept in RAM for the display
+//! and appended to ALARMS.LOG on the SD card
+
+use crate::alarm::AlarmEvent;
+use crate::ring_buffer::RingBuffer;
+use crate::units::Units;
+use core::fmt::{self, Write};
+use ds323x::{Datelike, NaiveDateTime, Timelike};
+use heapless::String;
+
+/// Events kept in RAM
+const LOG_LEN: usize = 16;
+
+const LOG_LINE_STRING_CAP: usize = 96;
+const CSV_HEADER_STRING_CAP: usize = 128;
+
+/// Units and column names, written as comment lines whenever storage is
+/// initialized
+pub fn csv_header(units: &Units) -> Result<String<CSV_HEADER_STRING_CAP>, fmt::Error> {
+    let mut s = String::new();
+    writeln!(&mut s, "#units {}", units)?;
+    writeln!(
+        &mut s,
+        "#timestamp,event,severity,channel,rate_window,value"
+    )?;
+    Ok(s)
+}
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub struct LoggedEvent {
+    pub datetime: NaiveDateTime,
+    pub event: AlarmEvent,
+}
+
+impl LoggedEvent {
+    /// Values are in the configured units, per rate window in minutes for
+    /// rate rules
+    pub fn to_csv_line(&self, units: &Units) -> Result<String<LOG_LINE_STRING_CAP>, fmt::Error> {
+        let mut s = String::new();
+        let date = self.datetime.date();
+        let time = self.datetime.time();
+        write!(
+            &mut s,
+            "{}-{:02}-{:02}T{:02}:{:02}:{:02},{},{},",
+            date.year(),
+            date.month(),
+            date.day(),
+            time.hour(),
+            time.minute(),
+            time.second(),
+            self.event.kind.name(),
+            self.event.severity.name(),
+        )?;
+        if let Some(rule) = &self.event.rule {
+            write!(&mut s, "{}", rule.channel.name())?;
+        }
+        s.push(',').map_err(|_| fmt::Error)?;
+        if let Some(window) = self.event.rule.and_then(|rule| rule.rate_window) {
+            write!(&mut s, "{}", window.0)?;
+        }
+        s.push(',').map_err(|_| fmt::Error)?;
+        if let (Some(rule), Some(value)) = (&self.event.rule, self.event.value) {
+            let value = if rule.rate_window.is_some() {
+                units.convert_difference(rule.channel, value)
+            } else {
+                units.convert(rule.channel, value)
+            };
+            write!(&mut s, "{:.*}", units.decimals(rule.channel), value)?;
+        }
+        writeln!(&mut s)?;
+        Ok(s)
+    }
+}
+
+pub struct AlarmLog {
+    events: RingBuffer<Option<LoggedEvent>, LOG_LEN>,
+}
+
+impl AlarmLog {
+    pub fn new() -> Self {
+        AlarmLog {
+            events: RingBuffer::new(),
+        }
+    }
+
+    pub fn push(&mut self, datetime: &NaiveDateTime, event: &AlarmEvent) -> LoggedEvent {
+        let logged = LoggedEvent {
+            datetime: *datetime,
+            event: *event,
+        };
+        self.events.push(Some(logged));
+        logged
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn newest_first(&self) -> impl Iterator<Item = LoggedEvent> + '_ {
+        self.events.iter().rev().flatten()
+    }
+}
+
+impl Default for AlarmLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alarm::{AlarmRule, Channel, Comparison, EventKind, Severity};
+    use crate::units::{GasResistanceUnit, PressureUnit, TemperatureUnit};
+    use ds323x::NaiveDate;
+    use embedded_time::duration::{Minutes, Seconds};
+
+    const UNITS: Units = Units {
+        temperature: TemperatureUnit::Fahrenheit,
+        pressure: PressureUnit::Hectopascal,
+        gas_resistance: GasResistanceUnit::Ohm,
+    };
+
+    fn column_count(line: &str) -> usize {
+        line.trim_end().split(',').count()
+    }
+
+    fn event(channel: Channel, rate_window: Option<Minutes>, value: f32) -> LoggedEvent {
+        LoggedEvent {
+            datetime: NaiveDate::from_ymd(2021, 6, 1).and_hms(12, 30, 5),
+            event: AlarmEvent {
+                kind: EventKind::Raised,
+                severity: Severity::Warning,
+                rule: Some(AlarmRule {
+                    channel,
+                    comparison: Comparison::Above(1.0),
+                    severity: Severity::Warning,
+                    hysteresis: 0.0,
+                    trigger_delay: Seconds(0),
+                    min_on_time: Seconds(0),
+                    rate_window,
+                }),
+                value: Some(value),
+            },
+        }
+    }
+
+    #[test]
+    fn lines_match_the_header() {
+        let header = csv_header(&UNITS).unwrap();
+        let columns = header.lines().last().map(column_count).unwrap();
+        for channel in Channel::ALL.iter() {
+            for rate_window in [None, Some(Minutes(180))] {
+                let line = event(*channel, rate_window, 1.0)
+                    .to_csv_line(&UNITS)
+                    .unwrap();
+                assert_eq!(column_count(&line), columns, "{}", line);
+            }
+        }
+        let acknowledged = LoggedEvent {
+            event: AlarmEvent {
+                kind: EventKind::Acknowledged,
+                rule: None,
+                value: None,
+                ..event(Channel::Temperature, None, 0.0).event
+            },
+            ..event(Channel::Temperature, None, 0.0)
+        };
+        let line = acknowledged.to_csv_line(&UNITS).unwrap();
+        assert_eq!(line, "2021-06-01T12:30:05,acknowledged,warning,,,\n");
+        assert_eq!(column_count(&line), columns);
+    }
+
+    #[test]
+    fn rate_values_are_converted_as_differences() {
+        let line = event(Channel::Temperature, None, 20.0)
+            .to_csv_line(&UNITS)
+            .unwrap();
+        assert_eq!(
+            line,
+            "2021-06-01T12:30:05,raised,warning,temperature,,68.00\n"
+        );
+        let line = event(Channel::Temperature, Some(Minutes(30)), 2.0)
+            .to_csv_line(&UNITS)
+            .unwrap();
+        assert_eq!(
+            line,
+            "2021-06-01T12:30:05,raised,warning,temperature,30,3.60\n"
+        );
+    }
+
+    #[test]
+    fn lines_are_capped() {
+        // Longest channel name and window with a large value fit
+        let line = event(Channel::SeaLevelPressure, Some(Minutes(180)), -99_999.99)
+            .to_csv_line(&UNITS)
+            .unwrap();
+        assert_eq!(
+            line,
+            "2021-06-01T12:30:05,raised,warning,sea_level_pressure,180,-99999.99\n"
+        );
+        let line = event(Channel::GasResistance, None, 4_000_000_000.0)
+            .to_csv_line(&UNITS)
+            .unwrap();
+        assert_eq!(
+            line,
+            "2021-06-01T12:30:05,raised,warning,gas_resistance,,4000000000\n"
+        );
+        // A value too long for the line is an error, not a truncated line
+        assert!(event(Channel::SeaLevelPressure, None, f32::MAX)
+            .to_csv_line(&UNITS)
+            .is_err());
+    }
+}
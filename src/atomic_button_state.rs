@@ -1,19 +1,85 @@
-use core::sync::atomic::{AtomicBool, Ordering::SeqCst};
+use crate::system_clock::TICK_HZ;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering::SeqCst};
 
+/// Presses shorter than this are contact bounce
+const DEBOUNCE_TICKS: u32 = 3;
+
+/// Held at least this long is a long press, reported while still held
+const LONG_PRESS_TICKS: u32 = 2 * TICK_HZ;
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum ButtonPress {
+    Short,
+    Long,
+}
+
+/// Button edges are recorded from the interrupt, presses are classified
+/// by polling from the main loop
 #[derive(Debug)]
-#[repr(transparent)]
-pub struct AtomicButtonState(AtomicBool);
+pub struct AtomicButtonState {
+    down: AtomicBool,
+    /// Tick the button went down
+    pressed_at: AtomicU32,
+    /// Long press already reported for the current press
+    long_reported: AtomicBool,
+    short_pending: AtomicBool,
+    /// Released after a long hold the main loop didn't poll in time
+    long_pending: AtomicBool,
+}
 
 impl AtomicButtonState {
     pub const fn new() -> Self {
-        AtomicButtonState(AtomicBool::new(false))
+        AtomicButtonState {
+            down: AtomicBool::new(false),
+            pressed_at: AtomicU32::new(0),
+            long_reported: AtomicBool::new(false),
+            short_pending: AtomicBool::new(false),
+            long_pending: AtomicBool::new(false),
+        }
     }
 
-    pub fn set(&self) {
-        self.0.store(true, SeqCst);
+    /// Call from the interrupt on the falling edge
+    pub fn press(&self, ticks: u32) {
+        if !self.down.swap(true, SeqCst) {
+            self.pressed_at.store(ticks, SeqCst);
+            self.long_reported.store(false, SeqCst);
+        }
     }
 
-    pub fn get_and_clear(&self) -> bool {
-        self.0.swap(false, SeqCst)
+    /// Call from the interrupt on the rising edge
+    pub fn release(&self, ticks: u32) {
+        if self.down.swap(false, SeqCst) {
+            let held = ticks.wrapping_sub(self.pressed_at.load(SeqCst));
+            if !self.long_reported.swap(true, SeqCst) {
+                if held >= LONG_PRESS_TICKS {
+                    self.long_pending.store(true, SeqCst);
+                } else if held >= DEBOUNCE_TICKS {
+                    self.short_pending.store(true, SeqCst);
+                }
+            }
+        }
+    }
+
+    pub fn poll(&self, ticks: u32) -> Option<ButtonPress> {
+        if self.down.load(SeqCst) && !self.long_reported.load(SeqCst) {
+            let held = ticks.wrapping_sub(self.pressed_at.load(SeqCst));
+            if held >= LONG_PRESS_TICKS {
+                self.long_reported.store(true, SeqCst);
+                return Some(ButtonPress::Long);
+            }
+        }
+        if self.long_pending.swap(false, SeqCst) {
+            Some(ButtonPress::Long)
+        } else if self.short_pending.swap(false, SeqCst) {
+            Some(ButtonPress::Short)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for AtomicButtonState {
+    fn default() -> Self {
+        Self::new()
     }
 }
@@ -0,0 +1,244 @@
+//! Sea-level pressure reduction, barometric trend and forecast
+
+use crate::ring_buffer::RingBuffer;
+use crate::system_clock::SystemClock;
+use core::fmt;
+use embedded_time::{duration::Minutes, Instant};
+use micromath::F32Ext;
+
+const HISTORY_INTERVAL: Minutes = Minutes(10_u32);
+
+/// 3 hours of history, both ends inclusive
+const HISTORY_LEN: usize = 19;
+
+/// Change over 3 hours considered steady, in hectopascal (hPa)
+const TREND_STEADY_HPA: f32 = 1.6;
+
+/// Reduce the station pressure to mean sea-level pressure (hPa),
+/// hypsometric formula using the current temperature
+pub fn sea_level_pressure(station_hpa: f32, altitude_m: f32, temp_c: f32) -> f32 {
+    let lapse = 0.0065 * altitude_m;
+    station_hpa * (1.0 - (lapse / (temp_c + lapse + 273.15))).powf(-5.257)
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Trend {
+    Rising,
+    Steady,
+    Falling,
+}
+
+impl fmt::Display for Trend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Trend::Rising => f.write_str("^"),
+            Trend::Steady => f.write_str("-"),
+            Trend::Falling => f.write_str("v"),
+        }
+    }
+}
+
+pub struct Barometer {
+    /// Sea-level pressure samples (hPa), HISTORY_INTERVAL apart
+    history: RingBuffer<f32, HISTORY_LEN>,
+    last_sample: Option<Instant<SystemClock>>,
+    /// Most recent sea-level pressure (hPa)
+    pressure: Option<f32>,
+}
+
+impl Barometer {
+    pub fn new() -> Self {
+        Barometer {
+            history: RingBuffer::new(),
+            last_sample: None,
+            pressure: None,
+        }
+    }
+
+    pub fn update(&mut self, now: &Instant<SystemClock>, sea_level_hpa: f32) {
+        self.pressure.replace(sea_level_hpa);
+        let sample = match self.last_sample {
+            None => true,
+            Some(last) => match now.checked_duration_since(&last) {
+                Some(dur) => dur >= HISTORY_INTERVAL.into(),
+                None => false,
+            },
+        };
+        if sample {
+            self.last_sample.replace(*now);
+            self.history.push(sea_level_hpa);
+        }
+    }
+
+    pub fn pressure(&self) -> Option<f32> {
+        self.pressure
+    }
+
+    /// Change over the last 3 hours (hPa), once that much history is available
+    pub fn change(&self) -> Option<f32> {
+        if self.history.is_full() {
+            Some(self.history.newest()? - self.history.oldest()?)
+        } else {
+            None
+        }
+    }
+
+    pub fn trend(&self) -> Option<Trend> {
+        self.change().map(|change| {
+            if change >= TREND_STEADY_HPA {
+                Trend::Rising
+            } else if change <= -TREND_STEADY_HPA {
+                Trend::Falling
+            } else {
+                Trend::Steady
+            }
+        })
+    }
+
+    /// Zambretti forecaster, simplified (no wind or season adjustments)
+    pub fn forecast(&self) -> Option<&'static str> {
+        let pressure = self.pressure?;
+        let (z, base, letters): (f32, i32, &[u8]) = match self.trend()? {
+            Trend::Falling => (127.0 - (0.12 * pressure), 1, b"BDHORUVXZ"),
+            Trend::Steady => (144.0 - (0.13 * pressure), 10, b"ABEKNPSWXZ"),
+            Trend::Rising => (185.0 - (0.16 * pressure), 20, b"ABCFGIJLMQTYZ"),
+        };
+        let index = (z.round() as i32 - base).clamp(0, letters.len() as i32 - 1) as usize;
+        let letter = letters[index] - b'A';
+        Some(FORECASTS[letter as usize])
+    }
+}
+
+impl Default for Barometer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Zambretti forecasts A through Z
+const FORECASTS: [&str; 26] = [
+    "Settled fine",
+    "Fine weather",
+    "Becoming fine",
+    "Fine, becoming less settled",
+    "Fine, possible showers",
+    "Fairly fine, improving",
+    "Fairly fine, possible showers early",
+    "Fairly fine, showery later",
+    "Showery early, improving",
+    "Changeable, mending",
+    "Fairly fine, showers likely",
+    "Rather unsettled, clearing later",
+    "Unsettled, probably improving",
+    "Showery, bright intervals",
+    "Showery, becoming less settled",
+    "Changeable, some rain",
+    "Unsettled, short fine intervals",
+    "Unsettled, rain later",
+    "Unsettled, some rain",
+    "Mostly very unsettled",
+    "Occasional rain, worsening",
+    "Rain at times, very unsettled",
+    "Rain at frequent intervals",
+    "Rain, very unsettled",
+    "Stormy, may improve",
+    "Stormy, much rain",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    fn at(sec: u32) -> Instant<SystemClock> {
+        Instant::new(sec)
+    }
+
+    /// Three hours of samples changing steadily by change (hPa) to pressure
+    fn barometer(pressure: f32, change: f32) -> Barometer {
+        let mut barometer = Barometer::new();
+        let last = HISTORY_LEN as u32 - 1;
+        for i in 0..=last {
+            let sample = pressure - change + change * i as f32 / last as f32;
+            barometer.update(&at(i * 600), sample);
+        }
+        barometer
+    }
+
+    #[test]
+    fn sea_level_pressure_reference_values() {
+        // International Standard Atmosphere, 1013.25 hPa at sea level
+        assert_close(sea_level_pressure(954.61, 500.0, 11.75), 1013.25, 1.0);
+        assert_close(sea_level_pressure(898.75, 1000.0, 8.5), 1013.25, 1.0);
+        // Colder air is denser, the same station pressure reduces higher
+        assert_close(sea_level_pressure(898.7, 1000.0, 5.0), 1014.7, 1.0);
+        // At sea level nothing changes
+        assert_close(sea_level_pressure(1000.0, 0.0, 20.0), 1000.0, 0.01);
+    }
+
+    #[test]
+    fn trend_needs_three_hours() {
+        let mut barometer = Barometer::new();
+        for i in 0..HISTORY_LEN as u32 - 1 {
+            barometer.update(&at(i * 600), 1013.0 + i as f32);
+        }
+        assert_eq!(barometer.pressure(), Some(1030.0));
+        assert_eq!(barometer.change(), None);
+        assert_eq!(barometer.trend(), None);
+        assert_eq!(barometer.forecast(), None);
+    }
+
+    #[test]
+    fn samples_are_ten_minutes_apart() {
+        let mut barometer = Barometer::new();
+        // Updates in between only replace the current pressure
+        for i in 0..(HISTORY_LEN as u32 - 1) * 10 {
+            barometer.update(&at(i * 60), 1013.0);
+        }
+        assert_eq!(barometer.change(), None);
+        barometer.update(&at((HISTORY_LEN as u32 - 1) * 600), 1016.0);
+        assert_close(barometer.change().unwrap(), 3.0, 0.001);
+    }
+
+    #[test]
+    fn trend_thresholds() {
+        assert_eq!(barometer(1013.0, 1.7).trend(), Some(Trend::Rising));
+        assert_eq!(barometer(1013.0, 1.5).trend(), Some(Trend::Steady));
+        assert_eq!(barometer(1013.0, 0.0).trend(), Some(Trend::Steady));
+        assert_eq!(barometer(1013.0, -1.5).trend(), Some(Trend::Steady));
+        assert_eq!(barometer(1013.0, -1.7).trend(), Some(Trend::Falling));
+    }
+
+    #[test]
+    fn zambretti_forecasts() {
+        // Z = 185 - 0.16 P = 20.2, A
+        assert_eq!(barometer(1030.0, 3.0).forecast(), Some("Settled fine"));
+        // Z = 144 - 0.13 P = 12.3, the third steady letter E
+        assert_eq!(
+            barometer(1013.0, 0.0).forecast(),
+            Some("Fine, possible showers")
+        );
+        // Z = 127 - 0.12 P = 3.4, the third falling letter H
+        assert_eq!(
+            barometer(1030.0, -3.0).forecast(),
+            Some("Fairly fine, showery later")
+        );
+        // Z = 8.2, the eighth falling letter X
+        assert_eq!(
+            barometer(990.0, -3.0).forecast(),
+            Some("Rain, very unsettled")
+        );
+        // Past either end of the scale
+        assert_eq!(barometer(1100.0, 3.0).forecast(), Some("Settled fine"));
+        assert_eq!(barometer(900.0, -3.0).forecast(), Some("Stormy, much rain"));
+    }
+}
@@ -0,0 +1,176 @@
+//! Non-blocking buzzer pattern player, driven by the SysTick ticks
+
+use crate::alarm::Severity;
+use crate::config;
+use crate::system_clock::TICK_HZ;
+use embedded_hal::PwmPin;
+
+/// Buzzer on or off for a number of milliseconds
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+struct Step {
+    on: bool,
+    ms: u16,
+}
+
+impl Step {
+    const fn on(ms: u16) -> Self {
+        Step { on: true, ms }
+    }
+
+    const fn off(ms: u16) -> Self {
+        Step { on: false, ms }
+    }
+
+    fn ticks(&self) -> u32 {
+        ((u32::from(self.ms) * TICK_HZ) / 1000).max(1)
+    }
+}
+
+const BOOT: &[Step] = &[Step::on(200)];
+const CHIRP: &[Step] = &[Step::on(40), Step::off(60), Step::on(40)];
+const WARNING: &[Step] = &[
+    Step::on(150),
+    Step::off(150),
+    Step::on(150),
+    Step::off(1550),
+];
+const CRITICAL: &[Step] = &[Step::on(1000)];
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Pattern {
+    /// Single beep on power up
+    Boot,
+    /// Short double chirp, acknowledges a button press
+    Chirp,
+    /// Intermittent double beep every 2 seconds
+    Warning,
+    /// Continuous tone
+    Critical,
+}
+
+impl Pattern {
+    /// Pattern sounded while an alarm of the given severity is active
+    pub fn for_severity(severity: Severity) -> Option<Self> {
+        match severity {
+            Severity::Info => None,
+            Severity::Warning => Some(Pattern::Warning),
+            Severity::Critical => Some(Pattern::Critical),
+        }
+    }
+
+    fn steps(self) -> &'static [Step] {
+        match self {
+            Pattern::Boot => BOOT,
+            Pattern::Chirp => CHIRP,
+            Pattern::Warning => WARNING,
+            Pattern::Critical => CRITICAL,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+struct Playing {
+    pattern: Pattern,
+    step: usize,
+    /// Tick the current step started at, None until the next update
+    step_start: Option<u32>,
+}
+
+impl Playing {
+    fn new(pattern: Pattern) -> Self {
+        Playing {
+            pattern,
+            step: 0,
+            step_start: None,
+        }
+    }
+
+    /// Returns whether the buzzer should be on, None once a one-shot
+    /// pattern has finished
+    fn update(&mut self, ticks: u32, repeat: bool) -> Option<bool> {
+        let steps = self.pattern.steps();
+        let step_start = *self.step_start.get_or_insert(ticks);
+        if ticks.wrapping_sub(step_start) >= steps[self.step].ticks() {
+            self.step += 1;
+            self.step_start = Some(ticks);
+            if self.step == steps.len() {
+                if repeat {
+                    self.step = 0;
+                } else {
+                    return None;
+                }
+            }
+        }
+        Some(steps[self.step].on)
+    }
+}
+
+/// Plays a repeating alarm pattern, which one-shot cues interrupt
+pub struct Buzzer<PWM> {
+    pwm: PWM,
+    on: bool,
+    alarm: Option<Playing>,
+    cue: Option<Playing>,
+}
+
+impl<PWM> Buzzer<PWM>
+where
+    PWM: PwmPin<Duty = u16>,
+{
+    pub fn new(mut pwm: PWM) -> Self {
+        // A square wave (50% duty) is the loudest
+        let max_duty = u32::from(pwm.get_max_duty());
+        let volume = u32::from(config::BUZZER_VOLUME_PERCENT.min(100));
+        pwm.set_duty(((max_duty / 2) * volume / 100) as u16);
+        pwm.disable();
+        Buzzer {
+            pwm,
+            on: false,
+            alarm: None,
+            cue: None,
+        }
+    }
+
+    /// Repeat the pattern until changed, restarts only if it differs from
+    /// the current alarm pattern
+    pub fn set_alarm(&mut self, pattern: Option<Pattern>) {
+        if self.alarm.map(|p| p.pattern) != pattern {
+            self.alarm = pattern.map(Playing::new);
+        }
+    }
+
+    /// Play the pattern once, the alarm pattern resumes afterwards
+    pub fn play(&mut self, pattern: Pattern) {
+        self.cue.replace(Playing::new(pattern));
+    }
+
+    /// Call on every tick
+    pub fn update(&mut self, ticks: u32) {
+        let mut on = None;
+        if let Some(cue) = &mut self.cue {
+            on = cue.update(ticks, false);
+            if on.is_none() {
+                self.cue = None;
+                // Start the alarm pattern over once the cue is done
+                self.alarm = self.alarm.map(|p| Playing::new(p.pattern));
+            }
+        }
+        if on.is_none() {
+            if let Some(alarm) = &mut self.alarm {
+                on = alarm.update(ticks, true);
+            }
+        }
+        self.set_on_off(on.unwrap_or(false));
+    }
+
+    fn set_on_off(&mut self, on: bool) {
+        if on != self.on {
+            if on {
+                self.pwm.enable();
+            } else {
+                self.pwm.disable();
+            }
+            self.on = on;
+        }
+    }
+}
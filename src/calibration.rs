@@ -0,0 +1,187 @@
+//! Per-channel offset and gain calibration
+
+use crate::sensor::Measurement;
+use core::fmt;
+use micromath::F32Ext;
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Channel {
+    /// Degree celsius (°C)
+    Temperature,
+    /// % relative humidity
+    Humidity,
+    /// Hectopascal (hPa)
+    Pressure,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 3] = [Channel::Temperature, Channel::Humidity, Channel::Pressure];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Channel::Temperature => "temperature",
+            Channel::Humidity => "humidity",
+            Channel::Pressure => "pressure",
+        }
+    }
+
+    pub fn value(self, data: &Measurement) -> f32 {
+        match self {
+            Channel::Temperature => data.temperature,
+            Channel::Humidity => data.humidity,
+            Channel::Pressure => data.pressure,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// corrected = (raw * gain) + offset
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub struct ChannelCalibration {
+    pub gain: f32,
+    pub offset: f32,
+}
+
+impl ChannelCalibration {
+    pub const IDENTITY: Self = ChannelCalibration {
+        gain: 1.0,
+        offset: 0.0,
+    };
+
+    pub const fn offset(offset: f32) -> Self {
+        ChannelCalibration { gain: 1.0, offset }
+    }
+
+    pub fn apply(&self, raw: f32) -> f32 {
+        (raw * self.gain) + self.offset
+    }
+
+    /// Single-point calibration, keeps the gain and moves the offset so the
+    /// raw value reads as the reference
+    pub fn set_offset(&mut self, raw: f32, reference: f32) {
+        self.offset = reference - (raw * self.gain);
+    }
+
+    /// Fit through two (raw, reference) points, None if the raw values are
+    /// too close together to give a meaningful gain
+    pub fn from_two_points(low: (f32, f32), high: (f32, f32)) -> Option<Self> {
+        let (raw_lo, ref_lo) = low;
+        let (raw_hi, ref_hi) = high;
+        let raw_span = raw_hi - raw_lo;
+        if raw_span.abs() < 0.1 {
+            return None;
+        }
+        let gain = (ref_hi - ref_lo) / raw_span;
+        Some(ChannelCalibration {
+            gain,
+            offset: ref_lo - (gain * raw_lo),
+        })
+    }
+}
+
+impl Default for ChannelCalibration {
+    fn default() -> Self {
+        ChannelCalibration::IDENTITY
+    }
+}
+
+impl fmt::Display for ChannelCalibration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.4} {:.3}", self.gain, self.offset)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug, Default)]
+pub struct Calibration {
+    pub temperature: ChannelCalibration,
+    pub humidity: ChannelCalibration,
+    pub pressure: ChannelCalibration,
+}
+
+impl Calibration {
+    pub fn channel(&self, channel: Channel) -> &ChannelCalibration {
+        match channel {
+            Channel::Temperature => &self.temperature,
+            Channel::Humidity => &self.humidity,
+            Channel::Pressure => &self.pressure,
+        }
+    }
+
+    pub fn channel_mut(&mut self, channel: Channel) -> &mut ChannelCalibration {
+        match channel {
+            Channel::Temperature => &mut self.temperature,
+            Channel::Humidity => &mut self.humidity,
+            Channel::Pressure => &mut self.pressure,
+        }
+    }
+
+    pub fn apply(&self, raw: &Measurement) -> Measurement {
+        Measurement {
+            temperature: self.temperature.apply(raw.temperature),
+            humidity: self.humidity.apply(raw.humidity).clamp(0.0, 100.0),
+            pressure: self.pressure.apply(raw.pressure),
+            gas_resistance: raw.gas_resistance,
+        }
+    }
+}
+
+impl fmt::Display for Calibration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, channel) in Channel::ALL.iter().enumerate() {
+            if i != 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{} {}", channel.name(), self.channel(*channel))?;
+        }
+        Ok(())
+    }
+}
+
+/// Two-point calibration procedure, the low point is captured first and the
+/// calibration is computed when the high point is captured
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug, Default)]
+pub struct TwoPointCalibration {
+    /// (raw, reference) per channel
+    low: [Option<(f32, f32)>; 3],
+}
+
+impl TwoPointCalibration {
+    pub fn set_low(&mut self, channel: Channel, raw: f32, reference: f32) {
+        self.low[channel.index()] = Some((raw, reference));
+    }
+
+    pub fn set_high(
+        &mut self,
+        channel: Channel,
+        raw: f32,
+        reference: f32,
+    ) -> Option<ChannelCalibration> {
+        let low = self.low[channel.index()].take()?;
+        ChannelCalibration::from_two_points(low, (raw, reference))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_offset_keeps_the_gain() {
+        let mut cal = ChannelCalibration::from_two_points((10.0, 11.0), (30.0, 33.0)).unwrap();
+        let gain = cal.gain;
+        cal.set_offset(20.0, 21.0);
+        assert_eq!(cal.gain, gain);
+        assert!((cal.apply(20.0) - 21.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn two_points_too_close_together() {
+        assert_eq!(
+            ChannelCalibration::from_two_points((20.0, 20.0), (20.05, 25.0)),
+            None
+        );
+    }
+}
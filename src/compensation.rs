@@ -0,0 +1,191 @@
+//! Self-heating compensation for the enclosed sensor
+//!
+//! The BME680 shares the enclosure with the MCU, regulator and OLED, so it
+//! reads high by an amount that depends on how much heat those dissipate.
+//! The correction term is a linear model of a few proxies for that heat,
+//! the coefficients are fit on the host by `plotter/fit-self-heating`.
+
+use crate::psychro;
+use crate::sensor::Measurement;
+use micromath::F32Ext;
+
+/// Convert a raw ADC sample of the internal temperature sensor to °C,
+/// using the factory calibration values (samples at 30 °C and 110 °C)
+pub fn mcu_temperature(sample: u16, cal30: u16, cal110: u16) -> f32 {
+    let (cal30, cal110) = (f32::from(cal30), f32::from(cal110));
+    ((110.0 - 30.0) * (f32::from(sample) - cal30) / (cal110 - cal30)) + 30.0
+}
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug, Default)]
+pub struct HeatSources {
+    /// MCU internal temperature sensor in degree celsius (°C)
+    pub mcu_temperature: f32,
+
+    /// DS3231 temperature in degree celsius (°C)
+    pub rtc_temperature: f32,
+
+    /// Display brightness, 0.0 (dimmest) ..= 1.0 (brightest)
+    pub brightness: f32,
+
+    pub uptime_sec: u32,
+}
+
+/// correction (°C) = constant
+///     + mcu_delta * (mcu_temperature - rtc_temperature)
+///     + brightness * brightness
+///     + warm_up * (1 - exp(-uptime / warm_up_tau_sec))
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub struct SelfHeatingModel {
+    pub constant: f32,
+    pub mcu_delta: f32,
+    pub brightness: f32,
+    pub warm_up: f32,
+    pub warm_up_tau_sec: f32,
+}
+
+impl SelfHeatingModel {
+    /// No compensation
+    pub const NONE: Self = SelfHeatingModel {
+        constant: 0.0,
+        mcu_delta: 0.0,
+        brightness: 0.0,
+        warm_up: 0.0,
+        warm_up_tau_sec: 1.0,
+    };
+
+    /// Correction term in °C, to be subtracted from the measured temperature
+    pub fn correction(&self, sources: &HeatSources) -> f32 {
+        let warm_up = 1.0 - (-(sources.uptime_sec as f32) / self.warm_up_tau_sec.max(1.0)).exp();
+        self.constant
+            + (self.mcu_delta * (sources.mcu_temperature - sources.rtc_temperature))
+            + (self.brightness * sources.brightness)
+            + (self.warm_up * warm_up)
+    }
+
+    pub fn compensate(&self, sources: &HeatSources, data: &Measurement) -> SelfHeating {
+        let correction = self.correction(sources);
+        let temperature = data.temperature - correction;
+
+        // The air at the sensor holds the same water vapor as the room, but
+        // relative to the saturation pressure at the warmer sensor temperature
+        let humidity = data.humidity * psychro::saturation_vapor_pressure(data.temperature)
+            / psychro::saturation_vapor_pressure(temperature);
+
+        SelfHeating {
+            sources: *sources,
+            correction,
+            measurement: Measurement {
+                temperature,
+                humidity: humidity.clamp(0.0, 100.0),
+                ..*data
+            },
+        }
+    }
+}
+
+impl Default for SelfHeatingModel {
+    fn default() -> Self {
+        SelfHeatingModel::NONE
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug, Default)]
+pub struct SelfHeating {
+    pub sources: HeatSources,
+
+    /// Correction term in °C that was subtracted from the temperature
+    pub correction: f32,
+
+    /// Compensated measurement
+    pub measurement: Measurement,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODEL: SelfHeatingModel = SelfHeatingModel {
+        constant: 0.5,
+        mcu_delta: 0.1,
+        brightness: 1.0,
+        warm_up: 2.0,
+        warm_up_tau_sec: 600.0,
+    };
+
+    const SOURCES: HeatSources = HeatSources {
+        mcu_temperature: 35.0,
+        rtc_temperature: 25.0,
+        brightness: 0.5,
+        uptime_sec: 0,
+    };
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    fn measurement(temperature: f32, humidity: f32) -> Measurement {
+        Measurement {
+            temperature,
+            humidity,
+            pressure: 1013.25,
+            gas_resistance: Some(50_000),
+        }
+    }
+
+    #[test]
+    fn mcu_temperature_interpolates_the_calibration() {
+        assert_eq!(mcu_temperature(940, 940, 1200), 30.0);
+        assert_eq!(mcu_temperature(1200, 940, 1200), 110.0);
+        assert_eq!(mcu_temperature(1070, 940, 1200), 70.0);
+        assert_close(mcu_temperature(907, 940, 1200), 19.85, 0.01);
+    }
+
+    #[test]
+    fn correction_terms() {
+        assert_close(MODEL.correction(&SOURCES), 2.0, 0.001);
+        let warmed_up = HeatSources {
+            uptime_sec: 600,
+            ..SOURCES
+        };
+        assert_close(MODEL.correction(&warmed_up), 3.264, 0.01);
+        let settled = HeatSources {
+            uptime_sec: 24 * 3600,
+            ..SOURCES
+        };
+        assert_close(MODEL.correction(&settled), 4.0, 0.001);
+        assert_eq!(SelfHeatingModel::NONE.correction(&settled), 0.0);
+    }
+
+    #[test]
+    fn compensate_cools_and_rescales_humidity() {
+        let data = measurement(25.0, 50.0);
+        let compensated = MODEL.compensate(&SOURCES, &data);
+        assert_eq!(compensated.sources, SOURCES);
+        assert_close(compensated.correction, 2.0, 0.001);
+        assert_close(compensated.measurement.temperature, 23.0, 0.001);
+        assert_close(compensated.measurement.humidity, 56.38, 0.05);
+        assert_eq!(compensated.measurement.pressure, data.pressure);
+        assert_eq!(compensated.measurement.gas_resistance, data.gas_resistance);
+    }
+
+    #[test]
+    fn compensated_humidity_is_clamped() {
+        let compensated = MODEL.compensate(&SOURCES, &measurement(25.0, 95.0));
+        assert_eq!(compensated.measurement.humidity, 100.0);
+    }
+
+    #[test]
+    fn none_passes_through() {
+        let data = measurement(25.0, 50.0);
+        let compensated = SelfHeatingModel::NONE.compensate(&SOURCES, &data);
+        assert_eq!(compensated.correction, 0.0);
+        assert_close(compensated.measurement.temperature, data.temperature, 0.0);
+        assert_close(compensated.measurement.humidity, data.humidity, 0.001);
+    }
+}
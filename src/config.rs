@@ -0,0 +1,340 @@
+//! Compile-time configuration
+
+use crate::actuator::{ActuatorConfig, MAX_ACTUATORS};
+use crate::alarm::{AlarmRule, Channel, Comparison, Severity};
+use crate::calibration::{Calibration, ChannelCalibration};
+use crate::compensation::SelfHeatingModel;
+use crate::display::{ClockFormat, TimeFormat, View};
+use crate::filter::FilterConfig;
+use crate::history::{GraphConfig, GraphStyle};
+use crate::schedule::{BrightnessLevel, DimmingSchedule, TimeOfDay, WeeklySchedule};
+use crate::sun::Location;
+use crate::units::{GasResistanceUnit, PressureUnit, TemperatureUnit, Units};
+use crate::view_mode_switcher::ViewSlot;
+use crate::warm_up::WarmUpConfig;
+use embedded_time::duration::{Minutes, Seconds};
+
+/// Append the derived psychrometric values (dew point, absolute humidity,
+/// heat index, humidex) to each record
+pub const RECORD_PSYCHROMETRICS: bool = true;
+
+/// Station altitude above mean sea level in meters, used to reduce the
+/// measured (station) pressure to sea-level pressure
+pub const STATION_ALTITUDE_M: f32 = 0.0;
+
+/// Append the sea-level pressure to each record
+pub const RECORD_SEA_LEVEL_PRESSURE: bool = true;
+
+/// Show a Zambretti forecast on the barometer view
+pub const BAROMETER_FORECAST: bool = true;
+
+/// Warm-up after the sensor is initialized, readings are marked as settling
+/// in the records per quantity and don't trigger alarms until the quantities
+/// of their channel have settled
+pub const WARM_UP: WarmUpConfig = WarmUpConfig {
+    temperature: Seconds(2 * 60_u32),
+    humidity: Seconds(2 * 60_u32),
+    pressure: Seconds(60_u32),
+    gas_resistance: Seconds(10 * 60_u32),
+};
+
+/// Units for the display, records and alarm thresholds in the settings
+/// file, overridden by `units.*` entries in the settings file
+pub const UNITS: Units = Units {
+    temperature: TemperatureUnit::Fahrenheit,
+    pressure: PressureUnit::Hectopascal,
+    gas_resistance: GasResistanceUnit::Ohm,
+};
+
+/// Time view format, overridden by `time.*` entries in the settings file
+pub const TIME_FORMAT: TimeFormat = TimeFormat {
+    clock: ClockFormat::TwelveHour,
+    seconds: false,
+    date: false,
+};
+
+/// Display rotation, views without data are skipped. Any View can be
+/// listed, e.g. one with its own render function.
+pub const VIEW_ROTATION: &[ViewSlot] = &[
+    ViewSlot::new(View::DASHBOARD, Seconds(10_u32)),
+    ViewSlot::new(View::TIME, Seconds(5_u32)),
+    ViewSlot::new(View::DATE, Seconds(5_u32)),
+    ViewSlot::new(View::SENSOR_READINGS, Seconds(5_u32)),
+    ViewSlot::new(View::PSYCHROMETRICS, Seconds(5_u32)),
+    ViewSlot::new(View::BAROMETER, Seconds(8_u32)),
+    ViewSlot::new(View::DAILY_EXTREMES, Seconds(5_u32)),
+    ViewSlot::new(View::graph::<0>(), Seconds(8_u32)),
+    ViewSlot::new(View::graph::<1>(), Seconds(8_u32)),
+    ViewSlot::new(View::graph::<2>(), Seconds(8_u32)),
+    ViewSlot::new(View::ALARM_LOG, Seconds(5_u32)),
+    ViewSlot::new(View::SYSTEM_STATUS, Seconds(5_u32)),
+];
+
+/// Show this view permanently instead of the rotation, e.g.
+/// Some(View::DASHBOARD). The rotation stands in while it has nothing to
+/// show, status changes still interrupt it.
+pub const PINNED_VIEW: Option<View> = None;
+
+/// Station location for the sunrise and sunset times of DIMMING levels
+/// relative to them, e.g. TimeOfDay::Sunset(60). The default, 0° N 0° E at
+/// UTC, has the sun rise and set at about 06:00 and 18:00 all year.
+pub const LOCATION: Location = Location {
+    latitude: 0.0,
+    longitude: 0.0,
+    utc_offset_min: 0,
+};
+
+/// Display contrast through the day, fading between levels. Dimmest from
+/// 20:00 to 08:00 by default.
+pub const DIMMING: DimmingSchedule = DimmingSchedule {
+    levels: &[
+        BrightnessLevel {
+            time: TimeOfDay::At { hour: 8, minute: 0 },
+            contrast: 255,
+        },
+        BrightnessLevel {
+            time: TimeOfDay::At {
+                hour: 20,
+                minute: 0,
+            },
+            contrast: 0,
+        },
+    ],
+    fade: Minutes(0_u32),
+};
+
+/// Move the view content by a pixel this often against OLED burn-in, None
+/// to disable
+pub const PIXEL_SHIFT_INTERVAL: Option<Minutes> = Some(Minutes(2_u32));
+
+/// Turn the screen off, e.g. overnight, no windows to keep it on
+pub const SCREEN_OFF: WeeklySchedule = WeeklySchedule {
+    weekday: None,
+    weekend: None,
+};
+
+/// The screen stays on this long after a button press while SCREEN_OFF
+pub const SCREEN_WAKE_DURATION: Seconds = Seconds(30_u32);
+
+/// Hours of history shown on the graph views
+pub const GRAPH_HOURS: u32 = 6;
+
+/// Channels with a graph view, at most history::MAX_GRAPH_CHANNELS, shown
+/// by View::graph::<index>() in VIEW_ROTATION
+pub const GRAPHS: &[GraphConfig] = &[
+    GraphConfig {
+        channel: Channel::Temperature,
+        style: GraphStyle::Line,
+        min_span: 1.0,
+    },
+    GraphConfig {
+        channel: Channel::Humidity,
+        style: GraphStyle::Line,
+        min_span: 5.0,
+    },
+    GraphConfig {
+        channel: Channel::SeaLevelPressure,
+        style: GraphStyle::Bar,
+        min_span: 4.0,
+    },
+];
+
+/// Record the unfiltered values (calibrated and self-heating compensated)
+/// instead of the filtered values
+pub const RECORD_UNFILTERED_VALUES: bool = false;
+
+/// Temperature (°C) filter stage
+pub const TEMPERATURE_FILTER: FilterConfig = FilterConfig {
+    max_step: Some(2.0),
+    median_len: 3,
+    ema_alpha: 1.0,
+};
+
+/// Humidity (%) filter stage
+pub const HUMIDITY_FILTER: FilterConfig = FilterConfig {
+    max_step: Some(10.0),
+    median_len: 3,
+    ema_alpha: 1.0,
+};
+
+/// Pressure (hPa) filter stage
+pub const PRESSURE_FILTER: FilterConfig = FilterConfig {
+    max_step: Some(3.0),
+    median_len: 3,
+    ema_alpha: 1.0,
+};
+
+/// Gas resistance (Ω) filter stage, the raw value is noisy while the heater
+/// settles
+pub const GAS_RESISTANCE_FILTER: FilterConfig = FilterConfig {
+    max_step: None,
+    median_len: 5,
+    ema_alpha: 0.3,
+};
+
+/// Default calibration, overridden by the settings file on the SD card.
+/// Applied on top of the -0.56 °C temperature offset set in the BME680
+/// driver, which also enters its humidity and pressure compensation.
+pub const CALIBRATION: Calibration = Calibration {
+    temperature: ChannelCalibration::IDENTITY,
+    humidity: ChannelCalibration::IDENTITY,
+    pressure: ChannelCalibration::IDENTITY,
+};
+
+/// Self-heating compensation, fit the coefficients with
+/// `plotter/fit-self-heating` from records logged with
+/// RECORD_SELF_HEATING enabled
+pub const SELF_HEATING: SelfHeatingModel = SelfHeatingModel::NONE;
+
+/// Append the self-heating model inputs and correction term to each record
+pub const RECORD_SELF_HEATING: bool = false;
+
+/// Buzzer tone frequency
+pub const BUZZER_FREQUENCY_HZ: u32 = 4000;
+
+/// Buzzer volume, 0 ..= 100, sets the PWM duty cycle
+pub const BUZZER_VOLUME_PERCENT: u16 = 100;
+
+/// Default time an alarm condition must hold before the rule triggers,
+/// for rules in the settings file that don't set `delay`
+pub const ALARM_TRIGGER_DELAY: Seconds = Seconds(60_u32);
+
+/// Default time a triggered rule stays active, for rules in the settings
+/// file that don't set `min_on`
+pub const ALARM_MIN_ON_TIME: Seconds = Seconds(120_u32);
+
+/// An acknowledged alarm stays silent this long, unless a more severe
+/// rule triggers
+pub const ALARM_SNOOZE: Seconds = Seconds(10 * 60_u32);
+
+/// Quiet hours, only alarms of at least QUIET_HOURS_MIN_SEVERITY sound, the
+/// rest are shown on the display and flash the LED. Off by default, e.g.
+/// `weekday: Some(TimeWindow::new(22, 0, 7, 0))` for 10 PM - 7 AM and
+/// `weekend: Some(TimeWindow::new(23, 0, 9, 0))` for 11 PM - 9 AM.
+pub const QUIET_HOURS: WeeklySchedule = WeeklySchedule {
+    weekday: None,
+    weekend: None,
+};
+
+pub const QUIET_HOURS_MIN_SEVERITY: Severity = Severity::Critical;
+
+/// Alarm rules of at least this severity preempt the display with a
+/// notification until it's dismissed, as do sensor and storage failures
+pub const NOTIFICATION_MIN_SEVERITY: Severity = Severity::Warning;
+
+/// Default alarm rules, overridden by `alarm.rule` entries in the settings
+/// file on the SD card. Thresholds are in the channel's internal units (°C, %, hPa).
+pub const ALARM_RULES: &[AlarmRule] = &[
+    // [68, 72] °F ([20, 22.2] °C) is ideal, alarm outside [66, 74] °F
+    AlarmRule {
+        channel: Channel::Temperature,
+        comparison: Comparison::Outside {
+            low: 18.89,
+            high: 23.33,
+        },
+        severity: Severity::Warning,
+        hysteresis: 0.3,
+        trigger_delay: ALARM_TRIGGER_DELAY,
+        min_on_time: ALARM_MIN_ON_TIME,
+        rate_window: None,
+    },
+    // Optional, condensation risk when the air cools within 4 °F of its dew
+    // point
+    // AlarmRule {
+    //     channel: Channel::DewPointSpread,
+    //     comparison: Comparison::Below(2.22),
+    //     severity: Severity::Warning,
+    //     hysteresis: 0.5,
+    //     trigger_delay: ALARM_TRIGGER_DELAY,
+    //     min_on_time: ALARM_MIN_ON_TIME,
+    //     rate_window: None,
+    // },
+    AlarmRule {
+        channel: Channel::Temperature,
+        comparison: Comparison::Below(10.0),
+        severity: Severity::Critical,
+        hysteresis: 0.5,
+        trigger_delay: Seconds(0_u32),
+        min_on_time: ALARM_MIN_ON_TIME,
+        rate_window: None,
+    },
+    AlarmRule {
+        channel: Channel::Humidity,
+        comparison: Comparison::Outside {
+            low: 25.0,
+            high: 65.0,
+        },
+        severity: Severity::Info,
+        hysteresis: 2.0,
+        trigger_delay: ALARM_TRIGGER_DELAY,
+        min_on_time: ALARM_MIN_ON_TIME,
+        rate_window: None,
+    },
+    // A window left open in winter, falling faster than 3 °F per 10 minutes
+    AlarmRule {
+        channel: Channel::Temperature,
+        comparison: Comparison::Below(-1.67),
+        severity: Severity::Warning,
+        hysteresis: 0.5,
+        trigger_delay: Seconds(0_u32),
+        min_on_time: ALARM_MIN_ON_TIME,
+        rate_window: Some(Minutes(10_u32)),
+    },
+    // Approaching storm, sea-level pressure falling faster than 6 hPa per 3 hours
+    AlarmRule {
+        channel: Channel::SeaLevelPressure,
+        comparison: Comparison::Below(-6.0),
+        severity: Severity::Info,
+        hysteresis: 1.0,
+        trigger_delay: Seconds(0_u32),
+        min_on_time: ALARM_MIN_ON_TIME,
+        rate_window: Some(Minutes(180_u32)),
+    },
+];
+
+/// Relay outputs on PB8, PB9 and PB12, None for an unused relay. Switched off
+/// while the sensor has failed.
+pub const ACTUATORS: [Option<ActuatorConfig>; MAX_ACTUATORS] = [
+    // Optional, space heater, on below 17.5 °C (63.5 °F) and off again at
+    // 18.5 °C
+    // Some(ActuatorConfig {
+    //     name: "heater",
+    //     control: Control::Loop {
+    //         channel: Channel::Temperature,
+    //         direction: Direction::Raise,
+    //         setpoint: 18.5,
+    //         hysteresis: 1.0,
+    //     },
+    //     min_cycle_time: Seconds(5 * 60_u32),
+    //     max_on_time: Some(Seconds(2 * 60 * 60_u32)),
+    //     active_low: false,
+    // }),
+    None,
+    // Optional, dehumidifier, on above 60 % and off again at 55 %
+    // Some(ActuatorConfig {
+    //     name: "dehumidifier",
+    //     control: Control::Loop {
+    //         channel: Channel::Humidity,
+    //         direction: Direction::Lower,
+    //         setpoint: 55.0,
+    //         hysteresis: 5.0,
+    //     },
+    //     min_cycle_time: Seconds(10 * 60_u32),
+    //     max_on_time: None,
+    //     active_low: false,
+    // }),
+    None,
+    // Optional, external siren or notifier, on while any critical alarm is
+    // active
+    // Some(ActuatorConfig {
+    //     name: "alarm_output",
+    //     control: Control::Alarm {
+    //         channel: None,
+    //         min_severity: Severity::Critical,
+    //     },
+    //     min_cycle_time: Seconds(0_u32),
+    //     max_on_time: None,
+    //     active_low: false,
+    // }),
+    None,
+];
@@ -0,0 +1,94 @@
+//! Line based command console on the debug serial port
+//!
+//! ```text
+//! cal                        show the active calibration
+//! cal <t|h|p> lo <ref>       capture the low point of a two-point calibration
+//! cal <t|h|p> hi <ref>       capture the high point and apply the calibration
+//! cal <t|h|p> offset <ref>   single-point offset calibration
+//! cal <t|h|p> reset          back to gain 1, offset 0
+//! ```
+//!
+//! Reference values are in the calibration units (°C, %, hPa).
+
+use crate::calibration::Channel;
+use heapless::String;
+
+const LINE_CAP: usize = 64;
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub enum Command {
+    ShowCalibration,
+    CalibrationLow { channel: Channel, reference: f32 },
+    CalibrationHigh { channel: Channel, reference: f32 },
+    CalibrationOffset { channel: Channel, reference: f32 },
+    CalibrationReset { channel: Channel },
+    Unknown,
+}
+
+impl Command {
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        let cmd = match fields.next()? {
+            "cal" => {
+                let channel = match fields.next() {
+                    None => return Some(Command::ShowCalibration),
+                    Some("t") => Channel::Temperature,
+                    Some("h") => Channel::Humidity,
+                    Some("p") => Channel::Pressure,
+                    Some(_) => return Some(Command::Unknown),
+                };
+                let action = fields.next();
+                let reference = fields.next().and_then(|f| f.parse::<f32>().ok());
+                match (action, reference) {
+                    (Some("lo"), Some(reference)) => Command::CalibrationLow { channel, reference },
+                    (Some("hi"), Some(reference)) => {
+                        Command::CalibrationHigh { channel, reference }
+                    }
+                    (Some("offset"), Some(reference)) => {
+                        Command::CalibrationOffset { channel, reference }
+                    }
+                    (Some("reset"), None) => Command::CalibrationReset { channel },
+                    _ => Command::Unknown,
+                }
+            }
+            _ => Command::Unknown,
+        };
+        Some(cmd)
+    }
+}
+
+pub struct Console {
+    line: String<LINE_CAP>,
+}
+
+impl Console {
+    pub fn new() -> Self {
+        Console {
+            line: String::new(),
+        }
+    }
+
+    /// Returns a command once a complete line has been received
+    pub fn push(&mut self, byte: u8) -> Option<Command> {
+        match byte {
+            b'\r' | b'\n' => {
+                let cmd = Command::parse(self.line.as_str());
+                self.line.clear();
+                cmd
+            }
+            _ => {
+                if byte.is_ascii() && self.line.push(byte as char).is_err() {
+                    // Overlong line, drop it
+                    self.line.clear();
+                }
+                None
+            }
+        }
+    }
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,339 @@
+//! Daily minimum and maximum temperature and humidity with the time they
+//! occurred, reset at local midnight
+
+use crate::alarm::Channel;
+use crate::sensor::Measurement;
+use crate::system_clock::SystemClock;
+use crate::units::Units;
+use crate::warm_up::{Quantity, WarmUp};
+use core::fmt::{self, Write};
+use ds323x::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use embedded_time::Instant;
+use heapless::String;
+
+const CSV_LINE_STRING_CAP: usize = 128;
+const CSV_HEADER_STRING_CAP: usize = 256;
+
+/// Units and column names, written as comment lines whenever storage is
+/// initialized
+pub fn csv_header(units: &Units) -> Result<String<CSV_HEADER_STRING_CAP>, fmt::Error> {
+    let mut s = String::new();
+    writeln!(&mut s, "#units {}", units)?;
+    writeln!(
+        &mut s,
+        "#date,temperature_min,temperature_min_time,temperature_max,temperature_max_time,\
+         humidity_min,humidity_min_time,humidity_max,humidity_max_time"
+    )?;
+    Ok(s)
+}
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub struct Extreme {
+    pub value: f32,
+    pub time: NaiveTime,
+}
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub struct Range {
+    pub min: Extreme,
+    pub max: Extreme,
+}
+
+impl Range {
+    fn new(value: f32, time: &NaiveTime) -> Self {
+        let extreme = Extreme { value, time: *time };
+        Range {
+            min: extreme,
+            max: extreme,
+        }
+    }
+
+    /// The first occurrence is kept on ties
+    fn update(&mut self, value: f32, time: &NaiveTime) {
+        if value < self.min.value {
+            self.min = Extreme { value, time: *time };
+        }
+        if value > self.max.value {
+            self.max = Extreme { value, time: *time };
+        }
+    }
+}
+
+fn update_range(range: &mut Option<Range>, value: f32, time: &NaiveTime) {
+    match range {
+        Some(range) => range.update(value, time),
+        None => {
+            range.replace(Range::new(value, time));
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug, Default)]
+pub struct DailyExtremes {
+    /// None until the RTC date is known
+    pub date: Option<NaiveDate>,
+    /// Temperature in degree celsius (°C)
+    pub temperature: Option<Range>,
+    /// Humidity in % relative humidity
+    pub humidity: Option<Range>,
+}
+
+impl DailyExtremes {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Call every second, returns the finished day's extremes on the first
+    /// call after midnight
+    pub fn roll_over(&mut self, date: &NaiveDate) -> Option<DailyExtremes> {
+        if self.date == Some(*date) {
+            return None;
+        }
+        let finished = core::mem::replace(
+            self,
+            DailyExtremes {
+                date: Some(*date),
+                ..Default::default()
+            },
+        );
+        if finished.date.is_some() && !finished.is_empty() {
+            Some(finished)
+        } else {
+            None
+        }
+    }
+
+    /// Call with each new measurement, readings of channels still warming
+    /// up are left out
+    pub fn update(
+        &mut self,
+        datetime: &NaiveDateTime,
+        now: &Instant<SystemClock>,
+        data: &Measurement,
+        warm_up: &WarmUp,
+    ) {
+        if self.date != Some(datetime.date()) {
+            return;
+        }
+        let time = datetime.time();
+        if warm_up.is_settled(Quantity::Temperature, now) {
+            update_range(&mut self.temperature, data.temperature, &time);
+        }
+        if warm_up.is_settled(Quantity::Humidity, now) {
+            update_range(&mut self.humidity, data.humidity, &time);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.temperature.is_none() && self.humidity.is_none()
+    }
+
+    /// Temperatures in the configured unit, empty fields for a channel
+    /// without readings
+    pub fn to_csv_line(&self, units: &Units) -> Result<String<CSV_LINE_STRING_CAP>, fmt::Error> {
+        let mut s = String::new();
+        if let Some(date) = &self.date {
+            write!(
+                &mut s,
+                "{}-{:02}-{:02}",
+                date.year(),
+                date.month(),
+                date.day()
+            )?;
+        }
+        let temperature = self.temperature.map(|range| Range {
+            min: Extreme {
+                value: units.convert(Channel::Temperature, range.min.value),
+                ..range.min
+            },
+            max: Extreme {
+                value: units.convert(Channel::Temperature, range.max.value),
+                ..range.max
+            },
+        });
+        for range in [temperature, self.humidity] {
+            match range {
+                Some(range) => {
+                    for extreme in [range.min, range.max] {
+                        write!(
+                            &mut s,
+                            ",{:.2},{:02}:{:02}:{:02}",
+                            extreme.value,
+                            extreme.time.hour(),
+                            extreme.time.minute(),
+                            extreme.time.second()
+                        )?;
+                    }
+                }
+                None => write!(&mut s, ",,,,")?,
+            }
+        }
+        writeln!(&mut s)?;
+        Ok(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::{GasResistanceUnit, PressureUnit, TemperatureUnit};
+    use crate::warm_up::WarmUpConfig;
+    use embedded_time::duration::Seconds;
+
+    const SETTLED: WarmUpConfig = WarmUpConfig {
+        temperature: Seconds(0),
+        humidity: Seconds(0),
+        pressure: Seconds(0),
+        gas_resistance: Seconds(0),
+    };
+
+    const UNITS: Units = Units {
+        temperature: TemperatureUnit::Fahrenheit,
+        pressure: PressureUnit::Hectopascal,
+        gas_resistance: GasResistanceUnit::Ohm,
+    };
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(2021, 6, day)
+    }
+
+    /// Readings on June 1st at the given hours, all settled
+    fn day(readings: &[(u32, f32, f32)]) -> DailyExtremes {
+        let mut extremes = DailyExtremes::new();
+        extremes.roll_over(&date(1));
+        let warm_up = WarmUp::new(SETTLED, &Instant::new(0));
+        for (hour, temperature, humidity) in readings.iter() {
+            let data = Measurement {
+                temperature: *temperature,
+                humidity: *humidity,
+                ..Default::default()
+            };
+            let datetime = date(1).and_hms(*hour, 0, 0);
+            extremes.update(&datetime, &Instant::new(*hour * 3600), &data, &warm_up);
+        }
+        extremes
+    }
+
+    fn extreme(value: f32, hour: u32) -> Extreme {
+        Extreme {
+            value,
+            time: NaiveTime::from_hms(hour, 0, 0),
+        }
+    }
+
+    #[test]
+    fn rolls_over_at_midnight() {
+        let mut extremes = day(&[(1, 20.0, 50.0), (13, 25.0, 40.0)]);
+        assert_eq!(extremes.roll_over(&date(1)), None);
+        let finished = extremes.roll_over(&date(2)).unwrap();
+        assert_eq!(finished.date, Some(date(1)));
+        assert_eq!(
+            finished.temperature,
+            Some(Range {
+                min: extreme(20.0, 1),
+                max: extreme(25.0, 13),
+            })
+        );
+        assert_eq!(extremes.date, Some(date(2)));
+        assert!(extremes.is_empty());
+    }
+
+    #[test]
+    fn first_day_starts_without_a_report() {
+        let mut extremes = DailyExtremes::new();
+        assert_eq!(extremes.roll_over(&date(1)), None);
+        assert_eq!(extremes.date, Some(date(1)));
+    }
+
+    #[test]
+    fn readings_of_another_day_are_ignored() {
+        let mut extremes = day(&[(1, 20.0, 50.0)]);
+        let warm_up = WarmUp::new(SETTLED, &Instant::new(0));
+        let data = Measurement {
+            temperature: 30.0,
+            ..Default::default()
+        };
+        extremes.update(&date(2).and_hms(0, 0, 5), &Instant::new(0), &data, &warm_up);
+        assert_eq!(extremes.temperature.unwrap().max, extreme(20.0, 1));
+    }
+
+    #[test]
+    fn ties_keep_the_first_occurrence() {
+        let extremes = day(&[
+            (1, 20.0, 50.0),
+            (2, 25.0, 60.0),
+            (3, 20.0, 50.0),
+            (4, 25.0, 60.0),
+        ]);
+        assert_eq!(
+            extremes.temperature,
+            Some(Range {
+                min: extreme(20.0, 1),
+                max: extreme(25.0, 2),
+            })
+        );
+        assert_eq!(
+            extremes.humidity,
+            Some(Range {
+                min: extreme(50.0, 1),
+                max: extreme(60.0, 2),
+            })
+        );
+    }
+
+    #[test]
+    fn empty_day_is_not_reported() {
+        let mut extremes = day(&[]);
+        assert!(extremes.is_empty());
+        assert_eq!(extremes.roll_over(&date(2)), None);
+        assert_eq!(extremes.date, Some(date(2)));
+    }
+
+    #[test]
+    fn unsettled_channels_are_left_out() {
+        let mut extremes = DailyExtremes::new();
+        extremes.roll_over(&date(1));
+        let warm_up = WarmUp::new(
+            WarmUpConfig {
+                humidity: Seconds(600),
+                ..SETTLED
+            },
+            &Instant::new(0),
+        );
+        let data = Measurement {
+            temperature: 20.0,
+            humidity: 50.0,
+            ..Default::default()
+        };
+        extremes.update(
+            &date(1).and_hms(0, 0, 30),
+            &Instant::new(30),
+            &data,
+            &warm_up,
+        );
+        assert!(extremes.temperature.is_some());
+        assert_eq!(extremes.humidity, None);
+    }
+
+    #[test]
+    fn csv_line() {
+        let header = csv_header(&UNITS).unwrap();
+        let columns = header.lines().last().unwrap().split(',').count();
+
+        let extremes = day(&[(1, 20.0, 50.0), (13, 25.0, 40.5)]);
+        let line = extremes.to_csv_line(&UNITS).unwrap();
+        assert_eq!(
+            line,
+            "2021-06-01,68.00,01:00:00,77.00,13:00:00,40.50,13:00:00,50.00,01:00:00\n"
+        );
+        assert_eq!(line.trim_end().split(',').count(), columns);
+
+        let missing_humidity = DailyExtremes {
+            humidity: None,
+            ..extremes
+        };
+        let line = missing_humidity.to_csv_line(&UNITS).unwrap();
+        assert_eq!(line, "2021-06-01,68.00,01:00:00,77.00,13:00:00,,,,\n");
+        assert_eq!(line.trim_end().split(',').count(), columns);
+    }
+}
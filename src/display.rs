@@ -1,41 +1,203 @@
+use crate::alarm::{AlarmStatus, Channel, Comparison};
+use crate::alarm_log::AlarmLog;
+use crate::barometer::{Barometer, Trend};
+use crate::config;
+use crate::daily_extremes::DailyExtremes;
+use crate::history::{ChannelHistory, GraphStyle, History, HISTORY_LEN, MAX_GRAPH_CHANNELS};
+use crate::notification::{Cause, Notification};
+use crate::psychro::Psychrometrics;
+use crate::sensor::{Measurement, SensorHealth};
+use crate::system_clock::SystemClock;
 use crate::system_status::SystemStatus;
+use crate::units::{PressureUnit, Units};
 use crate::util;
-use core::fmt::Write;
+use core::convert::Infallible;
+use core::fmt::{self, Write};
 use display_interface::DisplayError;
-use ds323x::{Datelike, NaiveDate, NaiveTime, Timelike};
-use embedded_graphics::mono_font::{MonoFont, MonoTextStyleBuilder};
+use ds323x::{Datelike, NaiveDate, NaiveDateTime, Timelike};
+use embedded_graphics::mono_font::{MonoFont, MonoTextStyle, MonoTextStyleBuilder};
 use embedded_graphics::{
+    image::{Image, ImageRaw},
     pixelcolor::BinaryColor,
     prelude::*,
-    text::{Baseline, Text},
+    primitives::{Line, PointsIter, PrimitiveStyle, Rectangle, Triangle},
+    text::{Alignment, Baseline, Text, TextStyleBuilder},
 };
+use embedded_time::Instant;
 use heapless::String;
 use ssd1306::{mode::BufferedGraphicsMode, prelude::*, Ssd1306};
 
 type DispSize = DisplaySize128x64;
 
+/// The views are laid out for the 128x64 display
+const WIDTH: i32 = DispSize::WIDTH as i32;
+const HEIGHT: i32 = DispSize::HEIGHT as i32;
+
 const DATE_FONT: MonoFont<'_> = profont::PROFONT_24_POINT;
 const TIME_FONT: MonoFont<'_> = profont::PROFONT_24_POINT;
+const TIME_DETAIL_FONT: MonoFont<'_> = profont::PROFONT_9_POINT;
 const SENSOR_READING_FONT: MonoFont<'_> = profont::PROFONT_14_POINT;
-const SYS_STATS_FONT: MonoFont<'_> = profont::PROFONT_14_POINT;
+const SYS_STATS_FONT: MonoFont<'_> = profont::PROFONT_9_POINT;
+const BAROMETER_FONT: MonoFont<'_> = profont::PROFONT_14_POINT;
+const FORECAST_FONT: MonoFont<'_> = profont::PROFONT_9_POINT;
+const ALARM_LOG_FONT: MonoFont<'_> = profont::PROFONT_9_POINT;
+const GRAPH_FONT: MonoFont<'_> = profont::PROFONT_9_POINT;
+const DAILY_EXTREMES_FONT: MonoFont<'_> = profont::PROFONT_9_POINT;
+const NOTIFICATION_FONT: MonoFont<'_> = profont::PROFONT_9_POINT;
+const DASHBOARD_SMALL_FONT: MonoFont<'_> = profont::PROFONT_9_POINT;
+const DASHBOARD_FONT: MonoFont<'_> = profont::PROFONT_14_POINT;
+const DASHBOARD_LARGE_FONT: MonoFont<'_> = profont::PROFONT_24_POINT;
+
+/// Status icons, 8x8 px, one byte per row with the MSB on the left
+const ICON_SIZE: i32 = 8;
+const SD_ICON: [u8; 8] = [0xF8, 0x84, 0xAA, 0xAA, 0x82, 0x82, 0x82, 0xFE];
+const BELL_ICON: [u8; 8] = [0x10, 0x38, 0x7C, 0x7C, 0x7C, 0xFE, 0x00, 0x10];
+const ERROR_ICON: [u8; 8] = [0xFE, 0xEE, 0xEE, 0xEE, 0xFE, 0xEE, 0xFE, 0x00];
 
 const LINE_BUF_CAP: usize = 64;
 
+/// Offsets cycled through every config::PIXEL_SHIFT_INTERVAL, a pixel around
+/// the layout position so little of the content is clipped
+const PIXEL_SHIFT_OFFSETS: [Point; 9] = [
+    Point::new(0, 0),
+    Point::new(1, 0),
+    Point::new(1, 1),
+    Point::new(0, 1),
+    Point::new(-1, 1),
+    Point::new(-1, 0),
+    Point::new(-1, -1),
+    Point::new(0, -1),
+    Point::new(1, -1),
+];
+
 const MONTHS: [&str; 12] = [
     "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
 ];
 
-pub enum View<'a> {
-    Time { data: &'a NaiveTime },
-    Date { data: &'a NaiveDate },
-    SensorReadings { data: &'a bme680::FieldData },
-    SystemStatus { data: &'a SystemStatus },
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+const GRAPH_NAMES: [&str; MAX_GRAPH_CHANNELS] = ["graph 0", "graph 1", "graph 2", "graph 3"];
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum ClockFormat {
+    /// 01:00 to 12:59 with an AM/PM marker
+    TwelveHour,
+    /// 00:00 to 23:59
+    TwentyFourHour,
+}
+
+impl ClockFormat {
+    pub const ALL: [ClockFormat; 2] = [ClockFormat::TwelveHour, ClockFormat::TwentyFourHour];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ClockFormat::TwelveHour => "12h",
+            ClockFormat::TwentyFourHour => "24h",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct TimeFormat {
+    pub clock: ClockFormat,
+    pub seconds: bool,
+    /// A line with the weekday and date under the time
+    pub date: bool,
+}
+
+/// Everything the views are drawn from
+pub struct ViewContext<'a> {
+    pub datetime: &'a NaiveDateTime,
+    /// None until there's a reading
+    pub sensor: Option<&'a Measurement>,
+    pub barometer: &'a Barometer,
+    pub daily_extremes: &'a DailyExtremes,
+    pub history: &'a History,
+    pub alarm_log: &'a AlarmLog,
+    pub status: &'a SystemStatus,
+    /// The notification to show, the highest priority pending one
+    pub notification: Option<&'a Notification>,
+}
+
+/// Draws a view onto the cleared frame, Ok(false) if it has nothing to show
+pub type RenderFn =
+    fn(&mut Renderer, &mut Frame<'_>, &ViewContext<'_>) -> Result<bool, RenderError<Infallible>>;
+
+/// A view shown by the switcher, registered by listing it in
+/// config::VIEW_ROTATION or passing it to set_mode or preempt. Views are
+/// told apart by name.
+#[derive(Copy, Clone, Debug)]
+pub struct View {
+    pub name: &'static str,
+    pub render: RenderFn,
+}
+
+impl View {
+    /// Time, temperature, humidity, pressure trend and status icons on one
+    /// page
+    pub const DASHBOARD: View = View::new("dashboard", render_dashboard);
+    pub const TIME: View = View::new("time", render_time);
+    pub const DATE: View = View::new("date", render_date);
+    pub const SENSOR_READINGS: View = View::new("sensor readings", render_sensor_readings);
+    pub const PSYCHROMETRICS: View = View::new("psychrometrics", render_psychrometrics);
+    pub const BAROMETER: View = View::new("barometer", render_barometer);
+    pub const DAILY_EXTREMES: View = View::new("daily extremes", render_daily_extremes);
+    pub const ALARM_LOG: View = View::new("alarm log", render_alarm_log);
+    pub const SYSTEM_STATUS: View = View::new("system status", render_system_status);
+    /// The current notification, shown by preempt
+    pub const NOTIFICATION: View = View::new("notification", render_notification);
+
+    pub const fn new(name: &'static str, render: RenderFn) -> Self {
+        View { name, render }
+    }
+
+    /// The graph of config::GRAPHS[INDEX]
+    pub const fn graph<const INDEX: usize>() -> Self {
+        View::new(GRAPH_NAMES[INDEX], render_graph::<INDEX>)
+    }
+}
+
+impl PartialEq for View {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for View {}
+
+#[derive(Debug)]
+pub enum RenderError<E> {
+    Draw(E),
+    /// A line didn't fit the line buffer
+    Formatting,
+}
+
+impl<E> From<fmt::Error> for RenderError<E> {
+    fn from(_: fmt::Error) -> Self {
+        RenderError::Formatting
+    }
+}
+
+impl From<RenderError<DisplayError>> for DisplayError {
+    fn from(e: RenderError<DisplayError>) -> Self {
+        match e {
+            RenderError::Draw(e) => e,
+            RenderError::Formatting => DisplayError::InvalidFormatError,
+        }
+    }
 }
 
 pub struct Display<DI> {
     drv: Ssd1306<DI, DispSize, BufferedGraphicsMode<DispSize>>,
-    brightness: Brightness,
-    line_buf: String<LINE_BUF_CAP>,
+    /// 0 (dimmest) ..= 255 (brightest)
+    contrast: u8,
+    on: bool,
+    /// Stays on until then during config::SCREEN_OFF
+    wake_until: Option<Instant<SystemClock>>,
+    /// Index into PIXEL_SHIFT_OFFSETS
+    shift: usize,
+    last_shift: Option<Instant<SystemClock>>,
+    renderer: Renderer,
 }
 
 impl<DI> Display<DI>
@@ -43,219 +205,998 @@ where
     DI: WriteOnlyDataCommand,
 {
     pub fn new(di: DI) -> Result<Self, DisplayError> {
-        let brightness = Brightness::BRIGHTEST;
         let mut drv =
             Ssd1306::new(di, DispSize {}, DisplayRotation::Rotate0).into_buffered_graphics_mode();
         drv.init()?;
         drv.set_display_on(true)?;
         drv.clear();
         drv.flush()?;
-        drv.set_brightness(brightness)?;
+        drv.set_brightness(Brightness::BRIGHTEST)?;
         Ok(Display {
             drv,
-            brightness,
-            line_buf: String::new(),
+            contrast: u8::MAX,
+            on: true,
+            wake_until: None,
+            shift: 0,
+            last_shift: None,
+            renderer: Renderer::new(),
         })
     }
 
-    /// 8 PM ..= 8 AM => Brightness::DIMMEST, else Brightness::BRIGHTEST
-    pub fn update_brightness(&mut self, time: &NaiveTime) -> Result<(), DisplayError> {
-        let hr24 = time.hour();
-        let range0 = 20..=23;
-        let range1 = 0..=7;
-        let brightness = if range0.contains(&hr24) || range1.contains(&hr24) {
-            Brightness::DIMMEST
-        } else {
-            Brightness::BRIGHTEST
+    pub fn set_units(&mut self, units: Units) {
+        self.renderer.set_units(units);
+    }
+
+    pub fn set_time_format(&mut self, format: TimeFormat) {
+        self.renderer.set_time_format(format);
+    }
+
+    /// Keep the screen on for config::SCREEN_WAKE_DURATION, call on button
+    /// presses. Returns true if the screen was off.
+    pub fn wake(&mut self, now: &Instant<SystemClock>) -> bool {
+        self.wake_until = now.checked_add(config::SCREEN_WAKE_DURATION);
+        !self.on
+    }
+
+    /// Call once a second
+    ///
+    /// Off during config::SCREEN_OFF unless woken, the contrast follows
+    /// config::DIMMING and the content shifts every
+    /// config::PIXEL_SHIFT_INTERVAL
+    pub fn update_brightness(
+        &mut self,
+        now: &Instant<SystemClock>,
+        datetime: &NaiveDateTime,
+    ) -> Result<(), DisplayError> {
+        let awake = match self.wake_until {
+            Some(until) => now.checked_duration_since(&until).is_none(),
+            None => false,
         };
-        if brightness != self.brightness {
+        let on = awake || !config::SCREEN_OFF.contains(datetime);
+        if on != self.on {
+            self.drv.set_display_on(on)?;
+            self.on = on;
+        }
+
+        if let Some(interval) = config::PIXEL_SHIFT_INTERVAL {
+            let shift = match self.last_shift {
+                Some(last) => match now.checked_duration_since(&last) {
+                    Some(dur) => dur >= interval.into(),
+                    None => false,
+                },
+                None => true,
+            };
+            if shift {
+                self.last_shift.replace(*now);
+                self.shift = (self.shift + 1) % PIXEL_SHIFT_OFFSETS.len();
+            }
+        }
+
+        let contrast = config::DIMMING.contrast(datetime, &config::LOCATION);
+        if contrast != self.contrast {
+            let brightness = if contrast == 0 {
+                Brightness::DIMMEST
+            } else {
+                Brightness::custom(0x2, contrast)
+            };
             self.drv.set_brightness(brightness)?;
-            self.brightness = brightness;
+            self.contrast = contrast;
         }
         Ok(())
     }
 
-    pub fn draw_view(&mut self, view: View) -> Result<(), DisplayError> {
-        match view {
-            View::Time { data } => self.draw_time(data),
-            View::Date { data } => self.draw_date(data),
-            View::SensorReadings { data } => self.draw_sensor_readings(data),
-            View::SystemStatus { data } => self.draw_system_status(data),
+    /// 0.0 (dimmest or off) ..= 1.0 (brightest)
+    pub fn brightness_level(&self) -> f32 {
+        if self.on {
+            f32::from(self.contrast) / 255.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns false if the view has nothing to show. Nothing is drawn
+    /// while the screen is off, the view counts as shown.
+    pub fn draw_view(&mut self, view: &View, context: &ViewContext) -> Result<bool, DisplayError> {
+        if !self.on {
+            return Ok(true);
         }
+        let offset = PIXEL_SHIFT_OFFSETS[self.shift];
+        let shown = self.renderer.render(&mut self.drv, offset, view, context)?;
+        if shown {
+            self.drv.flush()?;
+        }
+        Ok(shown)
+    }
+}
+
+/// Draws the views onto any binary color target, the display's frame
+/// buffer or e.g. an embedded-graphics MockDisplay
+pub struct Renderer {
+    line_buf: String<LINE_BUF_CAP>,
+    units: Units,
+    time_format: TimeFormat,
+    /// Notification view colors, flipped on each draw
+    inverted: bool,
+}
+
+impl Renderer {
+    /// Starts with config::UNITS and config::TIME_FORMAT
+    pub fn new() -> Self {
+        Renderer {
+            line_buf: String::new(),
+            units: config::UNITS,
+            time_format: config::TIME_FORMAT,
+            inverted: false,
+        }
+    }
+
+    pub fn set_units(&mut self, units: Units) {
+        self.units = units;
     }
 
-    fn draw_time(&mut self, time: &NaiveTime) -> Result<(), DisplayError> {
+    pub fn set_time_format(&mut self, format: TimeFormat) {
+        self.time_format = format;
+    }
+
+    /// Clears the target and draws the view moved by the offset, returns
+    /// false if it has nothing to show
+    pub fn render<D>(
+        &mut self,
+        target: &mut D,
+        offset: Point,
+        view: &View,
+        context: &ViewContext,
+    ) -> Result<bool, RenderError<D::Error>>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        target.clear(BinaryColor::Off).map_err(RenderError::Draw)?;
+        let mut canvas = ErasedTarget {
+            target,
+            error: None,
+        };
+        let result = (view.render)(
+            self,
+            &mut Frame {
+                canvas: &mut canvas,
+                offset,
+            },
+            context,
+        );
+        if let Some(e) = canvas.error {
+            return Err(RenderError::Draw(e));
+        }
+        match result {
+            Ok(shown) => Ok(shown),
+            Err(RenderError::Draw(never)) => match never {},
+            Err(RenderError::Formatting) => Err(RenderError::Formatting),
+        }
+    }
+
+    fn draw_dashboard<D>(
+        &mut self,
+        target: &mut D,
+        data: &ViewContext,
+    ) -> Result<(), RenderError<D::Error>>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let units = self.units;
+        let small_text_style = MonoTextStyleBuilder::new()
+            .font(&DASHBOARD_SMALL_FONT)
+            .text_color(BinaryColor::On)
+            .build();
         let text_style = MonoTextStyleBuilder::new()
-            .font(&TIME_FONT)
+            .font(&DASHBOARD_FONT)
             .text_color(BinaryColor::On)
             .build();
-        let pos_y = (DispSize::HEIGHT / 2) as i32;
-        let (_is_pm, hour) = time.hour12();
+        let large_text_style = MonoTextStyleBuilder::new()
+            .font(&DASHBOARD_LARGE_FONT)
+            .text_color(BinaryColor::On)
+            .build();
+        let small_char_width = (DASHBOARD_SMALL_FONT.character_size.width
+            + DASHBOARD_SMALL_FONT.character_spacing) as i32;
+        let large_char_width = (DASHBOARD_LARGE_FONT.character_size.width
+            + DASHBOARD_LARGE_FONT.character_spacing) as i32;
+
+        // Top bar, the time on the left and the status icons on the right
+        let time = data.datetime.time();
+        self.line_buf.clear();
+        match self.time_format.clock {
+            ClockFormat::TwelveHour => {
+                let (is_pm, hour) = time.hour12();
+                write!(
+                    &mut self.line_buf,
+                    "{:02}:{:02}{}",
+                    hour,
+                    time.minute(),
+                    if is_pm { "PM" } else { "AM" }
+                )?;
+            }
+            ClockFormat::TwentyFourHour => {
+                write!(
+                    &mut self.line_buf,
+                    "{:02}:{:02}",
+                    time.hour(),
+                    time.minute()
+                )?;
+            }
+        }
+        draw_text(
+            target,
+            &self.line_buf,
+            small_text_style,
+            Point::zero(),
+            Alignment::Left,
+            Baseline::Top,
+        )?;
 
-        self.drv.clear();
+        let status = data.status;
+        let error =
+            status.storage_error || status.storage_full || status.sensor_health != SensorHealth::Ok;
+        let (alarm, snoozed) = match status.alarm {
+            AlarmStatus::On(_) => (true, false),
+            AlarmStatus::Snoozed(_) => (true, true),
+            AlarmStatus::NotMonitoring | AlarmStatus::Off => (false, false),
+        };
+        // Right to left, a snoozed alarm's bell is struck through
+        let icons = [
+            (error, &ERROR_ICON, false),
+            (alarm, &BELL_ICON, snoozed),
+            (status.storage_connected, &SD_ICON, false),
+        ];
+        let mut pos_x = WIDTH - ICON_SIZE;
+        for (shown, icon, struck) in icons.iter() {
+            if !shown {
+                continue;
+            }
+            let raw: ImageRaw<'_, BinaryColor> = ImageRaw::new(*icon, ICON_SIZE as u32);
+            Image::new(&raw, Point::new(pos_x, 1))
+                .draw(target)
+                .map_err(RenderError::Draw)?;
+            if *struck {
+                Line::new(
+                    Point::new(pos_x, ICON_SIZE),
+                    Point::new(pos_x + ICON_SIZE - 1, 1),
+                )
+                .into_styled(PrimitiveStyle::with_stroke(BinaryColor::Off, 1))
+                .draw(target)
+                .map_err(RenderError::Draw)?;
+            }
+            pos_x -= ICON_SIZE + 2;
+        }
 
+        // Temperature in the large font with the unit after it
+        let top = DASHBOARD_SMALL_FONT.character_size.height as i32 + 2;
         self.line_buf.clear();
-        write!(&mut self.line_buf, "{:02}:{:02}", hour, time.minute(),)
-            .map_err(|_| DisplayError::InvalidFormatError)?;
-        Text::with_baseline(
-            self.line_buf.as_str(),
-            Point::new(24, pos_y),
+        match data.sensor {
+            Some(sensor) => write!(
+                &mut self.line_buf,
+                "{:.1}",
+                units
+                    .convert(Channel::Temperature, sensor.temperature)
+                    .clamp(-99.9, 999.9)
+            )?,
+            None => write!(&mut self.line_buf, "---")?,
+        }
+        draw_text(
+            target,
+            &self.line_buf,
+            large_text_style,
+            Point::new(0, top),
+            Alignment::Left,
+            Baseline::Top,
+        )?;
+        draw_text(
+            target,
+            units.symbol(Channel::Temperature),
             text_style,
-            Baseline::Middle,
-        )
-        .draw(&mut self.drv)?;
+            Point::new(self.line_buf.len() as i32 * large_char_width + 2, top),
+            Alignment::Left,
+            Baseline::Top,
+        )?;
 
-        self.drv.flush()?;
+        // Bottom row, the pressure with its trend and the humidity
+        if let Some(pressure) = data.barometer.pressure() {
+            self.line_buf.clear();
+            write!(
+                &mut self.line_buf,
+                "{:.*} {}",
+                units.decimals(Channel::SeaLevelPressure) - 1,
+                units
+                    .convert(Channel::SeaLevelPressure, pressure)
+                    .clamp(0.0, 9999.9),
+                units.symbol(Channel::SeaLevelPressure)
+            )?;
+            draw_text(
+                target,
+                &self.line_buf,
+                small_text_style,
+                Point::new(0, HEIGHT),
+                Alignment::Left,
+                Baseline::Bottom,
+            )?;
+            if let Some(trend) = data.barometer.trend() {
+                let pos = Point::new(
+                    self.line_buf.len() as i32 * small_char_width + 3,
+                    HEIGHT - ICON_SIZE - 1,
+                );
+                draw_trend_arrow(target, trend, pos, Size::new(8, 8))?;
+            }
+        }
+
+        if let Some(sensor) = data.sensor {
+            self.line_buf.clear();
+            write!(
+                &mut self.line_buf,
+                "{:.0}%",
+                sensor.humidity.clamp(0.0, 100.0)
+            )?;
+            draw_text(
+                target,
+                &self.line_buf,
+                text_style,
+                Point::new(WIDTH, HEIGHT),
+                Alignment::Right,
+                Baseline::Bottom,
+            )?;
+        }
 
         Ok(())
     }
 
-    fn draw_date(&mut self, date: &NaiveDate) -> Result<(), DisplayError> {
+    fn draw_time<D>(
+        &mut self,
+        target: &mut D,
+        datetime: &NaiveDateTime,
+    ) -> Result<(), RenderError<D::Error>>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let format = self.time_format;
         let text_style = MonoTextStyleBuilder::new()
-            .font(&DATE_FONT)
+            .font(&TIME_FONT)
+            .text_color(BinaryColor::On)
+            .build();
+        let detail_text_style = MonoTextStyleBuilder::new()
+            .font(&TIME_DETAIL_FONT)
             .text_color(BinaryColor::On)
             .build();
-        let mid = (DispSize::HEIGHT / 2) as i32;
+        let time = datetime.time();
+        let (is_pm, hour) = match format.clock {
+            ClockFormat::TwelveHour => {
+                let (is_pm, hour) = time.hour12();
+                (Some(is_pm), hour)
+            }
+            ClockFormat::TwentyFourHour => (None, time.hour()),
+        };
 
-        self.drv.clear();
+        // "hh:mm:ss" is 128 px wide
+        self.line_buf.clear();
+        write!(&mut self.line_buf, "{:02}:{:02}", hour, time.minute())?;
+        if format.seconds {
+            write!(&mut self.line_buf, ":{:02}", time.second())?;
+        }
+
+        // The AM/PM marker and the date share a line under the time, the
+        // two are centered together
+        let has_detail = is_pm.is_some() || format.date;
+        let dh = TIME_FONT.character_size.height as i32;
+        let detail_dh = TIME_DETAIL_FONT.character_size.height as i32 + 2;
+        let pos_y = if has_detail {
+            (HEIGHT - dh - detail_dh) / 2
+        } else {
+            (HEIGHT - dh) / 2
+        };
+        draw_text(
+            target,
+            &self.line_buf,
+            text_style,
+            Point::new(WIDTH / 2, pos_y),
+            Alignment::Center,
+            Baseline::Top,
+        )?;
+        if !has_detail {
+            return Ok(());
+        }
+
+        // At most 19 characters, "PM  Www DD Mmm YYYY"
+        self.line_buf.clear();
+        if let Some(is_pm) = is_pm {
+            write!(&mut self.line_buf, "{}", if is_pm { "PM" } else { "AM" })?;
+            if format.date {
+                write!(&mut self.line_buf, "  ")?;
+            }
+        }
+        if format.date {
+            let date = datetime.date();
+            write!(
+                &mut self.line_buf,
+                "{} {:02} {} {}",
+                WEEKDAYS[date.weekday().num_days_from_monday().clamp(0, 6) as usize],
+                date.day(),
+                MONTHS[date.month0().clamp(0, 11) as usize],
+                date.year().clamp(0, 9999)
+            )?;
+        }
+        draw_text(
+            target,
+            &self.line_buf,
+            detail_text_style,
+            Point::new(WIDTH / 2, pos_y + dh + 2),
+            Alignment::Center,
+            Baseline::Top,
+        )
+    }
+
+    fn draw_date<D>(
+        &mut self,
+        target: &mut D,
+        date: &NaiveDate,
+    ) -> Result<(), RenderError<D::Error>>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let text_style = MonoTextStyleBuilder::new()
+            .font(&DATE_FONT)
+            .text_color(BinaryColor::On)
+            .build();
 
         self.line_buf.clear();
         write!(
             &mut self.line_buf,
-            " {:02} {}",
+            "{:02} {}",
             date.day(),
             MONTHS[date.month0().clamp(0, 11) as usize],
-        )
-        .map_err(|_| DisplayError::InvalidFormatError)?;
-        Text::with_baseline(
-            self.line_buf.as_str(),
-            Point::zero(),
+        )?;
+        draw_text(
+            target,
+            &self.line_buf,
             text_style,
+            Point::new(WIDTH / 2, 0),
+            Alignment::Center,
             Baseline::Top,
-        )
-        .draw(&mut self.drv)?;
+        )?;
 
         self.line_buf.clear();
-        write!(&mut self.line_buf, "  {}", date.year())
-            .map_err(|_| DisplayError::InvalidFormatError)?;
-        Text::with_baseline(
-            self.line_buf.as_str(),
-            Point::new(0, mid),
+        write!(&mut self.line_buf, "{}", date.year().clamp(0, 9999))?;
+        draw_text(
+            target,
+            &self.line_buf,
             text_style,
+            Point::new(WIDTH / 2, HEIGHT / 2),
+            Alignment::Center,
             Baseline::Top,
         )
-        .draw(&mut self.drv)?;
+    }
 
-        self.drv.flush()?;
+    fn draw_sensor_readings<D>(
+        &mut self,
+        target: &mut D,
+        data: &Measurement,
+    ) -> Result<(), RenderError<D::Error>>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let units = self.units;
+        let temp = units
+            .convert(Channel::Temperature, data.temperature)
+            .clamp(-99.0, 999.0);
+        let humid = data.humidity.clamp(0.0, 99.0);
+        let pressure = units
+            .convert(Channel::Pressure, data.pressure)
+            .clamp(0.0, 9999.0);
+        // Whole hPa and mmHg, inches need the decimals
+        let pressure_decimals = match units.pressure {
+            PressureUnit::InchOfMercury => 2,
+            _ => 0,
+        };
+        let gas = units
+            .convert(
+                Channel::GasResistance,
+                data.gas_resistance.unwrap_or(0).clamp(0, 999999) as f32,
+            )
+            .min(999999.0);
 
-        Ok(())
+        let dh = HEIGHT / 4;
+        let text_style = MonoTextStyleBuilder::new()
+            .font(&SENSOR_READING_FONT)
+            .text_color(BinaryColor::On)
+            .build();
+
+        self.line_buf.clear();
+        write!(
+            &mut self.line_buf,
+            "{:.0} {}",
+            temp,
+            units.symbol(Channel::Temperature)
+        )?;
+        self.draw_label_value(target, text_style, "Temp", 0)?;
+
+        self.line_buf.clear();
+        write!(&mut self.line_buf, "{:.0} %", humid)?;
+        self.draw_label_value(target, text_style, "Humidity", dh)?;
+
+        self.line_buf.clear();
+        write!(&mut self.line_buf, "{:.*}", pressure_decimals, pressure)?;
+        self.draw_label_value(target, text_style, "Press", 2 * dh)?;
+
+        self.line_buf.clear();
+        write!(
+            &mut self.line_buf,
+            "{:.*}",
+            units.decimals(Channel::GasResistance),
+            gas
+        )?;
+        self.draw_label_value(target, text_style, "Gas", 3 * dh)
     }
 
-    fn draw_sensor_readings(&mut self, data: &bme680::FieldData) -> Result<(), DisplayError> {
-        let temp = util::celsius_to_fahrenheit(data.temperature_celsius()).clamp(0.0, 99.0);
-        let humid = data.humidity_percent().clamp(0.0, 99.0);
-        let pressure = data.pressure_hpa();
-        let gas = if data.gas_valid() {
-            data.gas_resistance_ohm()
-        } else {
-            0
-        };
+    fn draw_psychrometrics<D>(
+        &mut self,
+        target: &mut D,
+        data: &Psychrometrics,
+    ) -> Result<(), RenderError<D::Error>>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let units = self.units;
+        let dew_point = units
+            .convert(Channel::DewPoint, data.dew_point)
+            .clamp(-99.0, 999.0);
+        let abs_humid = data.absolute_humidity.clamp(0.0, 99.9);
+        let heat_index = units
+            .convert(Channel::HeatIndex, data.heat_index)
+            .clamp(-99.0, 999.0);
+        let humidex = data.humidex.clamp(-99.0, 999.0);
 
-        let dh = (DispSize::HEIGHT / 4) as i32;
+        let dh = HEIGHT / 4;
         let text_style = MonoTextStyleBuilder::new()
             .font(&SENSOR_READING_FONT)
             .text_color(BinaryColor::On)
             .build();
 
-        self.drv.clear();
+        self.line_buf.clear();
+        write!(
+            &mut self.line_buf,
+            "{:.0} {}",
+            dew_point,
+            units.symbol(Channel::DewPoint)
+        )?;
+        self.draw_label_value(target, text_style, "DewPt", 0)?;
+
+        self.line_buf.clear();
+        write!(&mut self.line_buf, "{:.1}", abs_humid)?;
+        self.draw_label_value(target, text_style, "AbsHum", dh)?;
 
         self.line_buf.clear();
-        write!(&mut self.line_buf, "Temp     {:02.0} F", temp)
-            .map_err(|_| DisplayError::InvalidFormatError)?;
-        Text::with_baseline(
-            self.line_buf.as_str(),
-            Point::zero(),
+        write!(
+            &mut self.line_buf,
+            "{:.0} {}",
+            heat_index,
+            units.symbol(Channel::HeatIndex)
+        )?;
+        self.draw_label_value(target, text_style, "HeatIx", 2 * dh)?;
+
+        self.line_buf.clear();
+        write!(&mut self.line_buf, "{:.0}", humidex)?;
+        self.draw_label_value(target, text_style, "Humidex", 3 * dh)
+    }
+
+    fn draw_barometer<D>(
+        &mut self,
+        target: &mut D,
+        data: &Barometer,
+    ) -> Result<(), RenderError<D::Error>>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let units = self.units;
+        let pressure = units
+            .convert(Channel::SeaLevelPressure, data.pressure().unwrap_or(0.0))
+            .clamp(0.0, 9999.9);
+        // One more decimal for inches, the same resolution as 0.1 hPa
+        let decimals = units.decimals(Channel::SeaLevelPressure) - 1;
+        let text_style = MonoTextStyleBuilder::new()
+            .font(&BAROMETER_FONT)
+            .text_color(BinaryColor::On)
+            .build();
+        let small_text_style = MonoTextStyleBuilder::new()
+            .font(&FORECAST_FONT)
+            .text_color(BinaryColor::On)
+            .build();
+        let small_dh = FORECAST_FONT.character_size.height as i32;
+        let dh = BAROMETER_FONT.character_size.height as i32;
+
+        self.line_buf.clear();
+        write!(
+            &mut self.line_buf,
+            "{:6.*} {}",
+            decimals,
+            pressure,
+            units.symbol(Channel::SeaLevelPressure)
+        )?;
+        draw_text(
+            target,
+            &self.line_buf,
             text_style,
+            Point::zero(),
+            Alignment::Left,
             Baseline::Top,
-        )
-        .draw(&mut self.drv)?;
+        )?;
+
+        // Trend arrow in the top right corner
+        if let Some(trend) = data.trend() {
+            draw_trend_arrow(target, trend, Point::new(WIDTH - 18, 1), Size::new(16, 14))?;
+        }
 
         self.line_buf.clear();
-        write!(&mut self.line_buf, "Humidity {:02.0} %", humid)
-            .map_err(|_| DisplayError::InvalidFormatError)?;
-        Text::with_baseline(
-            self.line_buf.as_str(),
+        if let Some(change) = data.change() {
+            write!(
+                &mut self.line_buf,
+                "3h {:+.*} {}",
+                decimals,
+                units
+                    .convert_difference(Channel::SeaLevelPressure, change)
+                    .clamp(-99.9, 99.9),
+                units.symbol(Channel::SeaLevelPressure)
+            )?;
+        } else {
+            write!(
+                &mut self.line_buf,
+                "3h --- {}",
+                units.symbol(Channel::SeaLevelPressure)
+            )?;
+        }
+        draw_text(
+            target,
+            &self.line_buf,
+            small_text_style,
             Point::new(0, dh),
+            Alignment::Left,
+            Baseline::Top,
+        )?;
+
+        if config::BAROMETER_FORECAST {
+            let char_width = FORECAST_FONT.character_size.width + FORECAST_FONT.character_spacing;
+            let max_chars = (WIDTH as u32 / char_width) as usize;
+            let mut rest = data.forecast().unwrap_or("Forecast pending");
+            let mut pos_y = dh + small_dh + 4;
+            // Word wrap
+            while !rest.is_empty() && pos_y < HEIGHT {
+                let (line, next) = if rest.len() <= max_chars {
+                    (rest, "")
+                } else {
+                    match rest[..=max_chars].rfind(' ') {
+                        Some(i) => (&rest[..i], &rest[i + 1..]),
+                        None => rest.split_at(max_chars),
+                    }
+                };
+                draw_text(
+                    target,
+                    line,
+                    small_text_style,
+                    Point::new(0, pos_y),
+                    Alignment::Left,
+                    Baseline::Top,
+                )?;
+                pos_y += small_dh;
+                rest = next;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn draw_daily_extremes<D>(
+        &mut self,
+        target: &mut D,
+        data: &DailyExtremes,
+    ) -> Result<(), RenderError<D::Error>>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let dh = HEIGHT / 5;
+        let text_style = MonoTextStyleBuilder::new()
+            .font(&DAILY_EXTREMES_FONT)
+            .text_color(BinaryColor::On)
+            .build();
+
+        draw_text(
+            target,
+            "TODAY",
             text_style,
+            Point::zero(),
+            Alignment::Left,
             Baseline::Top,
-        )
-        .draw(&mut self.drv)?;
+        )?;
+
+        let rows = [
+            (
+                "T ",
+                "lo",
+                Channel::Temperature,
+                data.temperature.map(|r| r.min),
+            ),
+            (
+                "  ",
+                "hi",
+                Channel::Temperature,
+                data.temperature.map(|r| r.max),
+            ),
+            ("RH", "lo", Channel::Humidity, data.humidity.map(|r| r.min)),
+            ("  ", "hi", Channel::Humidity, data.humidity.map(|r| r.max)),
+        ];
+        for (row, (symbol, label, channel, extreme)) in rows.iter().enumerate() {
+            self.line_buf.clear();
+            write!(&mut self.line_buf, "{} {}", symbol, label)?;
+            if let Some(extreme) = extreme {
+                write!(
+                    &mut self.line_buf,
+                    " {:5.1}{} {:02}:{:02}",
+                    self.units
+                        .convert(*channel, extreme.value)
+                        .clamp(-99.9, 999.9),
+                    self.units.symbol(*channel),
+                    extreme.time.hour(),
+                    extreme.time.minute()
+                )?;
+            } else {
+                write!(&mut self.line_buf, "   ---")?;
+            }
+            draw_text(
+                target,
+                &self.line_buf,
+                text_style,
+                Point::new(0, (row as i32 + 1) * dh),
+                Alignment::Left,
+                Baseline::Top,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn draw_graph<D>(
+        &mut self,
+        target: &mut D,
+        data: &ChannelHistory,
+    ) -> Result<(), RenderError<D::Error>>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let text_style = MonoTextStyleBuilder::new()
+            .font(&GRAPH_FONT)
+            .text_color(BinaryColor::On)
+            .build();
+        let line_style = PrimitiveStyle::with_stroke(BinaryColor::On, 1);
+        let channel = data.config().channel;
+        let units = self.units;
+
+        // Plot area right of the axis labels, one column per sample
+        let right = WIDTH - 1;
+        let left = right + 1 - HISTORY_LEN as i32;
+        let top = GRAPH_FONT.character_size.height as i32 + 2;
+        let bottom = HEIGHT - 1;
 
         self.line_buf.clear();
-        write!(&mut self.line_buf, "Pressure {:04.0}", pressure)
-            .map_err(|_| DisplayError::InvalidFormatError)?;
-        Text::with_baseline(
-            self.line_buf.as_str(),
-            Point::new(0, 2 * dh),
+        write!(
+            &mut self.line_buf,
+            "{} {}h",
+            channel.symbol(),
+            config::GRAPH_HOURS.clamp(0, 999)
+        )?;
+        if let Some(current) = data.current() {
+            write!(
+                &mut self.line_buf,
+                " {:.1}{}",
+                units.convert(channel, current).clamp(-9999.9, 999999.9),
+                units.symbol(channel)
+            )?;
+        }
+        draw_text(
+            target,
+            &self.line_buf,
             text_style,
+            Point::zero(),
+            Alignment::Left,
             Baseline::Top,
-        )
-        .draw(&mut self.drv)?;
+        )?;
 
-        self.line_buf.clear();
-        write!(&mut self.line_buf, "Gas    {:06.0}", gas)
-            .map_err(|_| DisplayError::InvalidFormatError)?;
-        Text::with_baseline(
-            self.line_buf.as_str(),
-            Point::new(0, 3 * dh),
+        Line::new(Point::new(left - 1, top), Point::new(left - 1, bottom))
+            .into_styled(line_style)
+            .draw(target)
+            .map_err(RenderError::Draw)?;
+
+        if let Some((low, high)) = data.range() {
+            // Axis labels, max at the top and min at the bottom
+            for (value, pos_y, baseline) in
+                [(high, top, Baseline::Top), (low, bottom, Baseline::Bottom)]
+            {
+                self.line_buf.clear();
+                write_compact(&mut self.line_buf, units.convert(channel, value))?;
+                draw_text(
+                    target,
+                    &self.line_buf,
+                    text_style,
+                    Point::new(0, pos_y),
+                    Alignment::Left,
+                    baseline,
+                )?;
+            }
+
+            let height = (bottom - top) as f32;
+            let to_y = |value: f32| {
+                let fraction = ((value - low) / (high - low)).clamp(0.0, 1.0);
+                bottom - (fraction * height) as i32
+            };
+            // Newest sample in the rightmost column
+            let mut pos_x = right + 1 - data.len() as i32;
+            let mut prev: Option<Point> = None;
+            for value in data.samples() {
+                let point = if value.is_finite() {
+                    Some(Point::new(pos_x, to_y(value)))
+                } else {
+                    None
+                };
+                match (data.config().style, prev, point) {
+                    (GraphStyle::Line, Some(prev), Some(point)) => {
+                        Line::new(prev, point)
+                            .into_styled(line_style)
+                            .draw(target)
+                            .map_err(RenderError::Draw)?;
+                    }
+                    (GraphStyle::Line, None, Some(point)) => {
+                        Pixel(point, BinaryColor::On)
+                            .draw(target)
+                            .map_err(RenderError::Draw)?;
+                    }
+                    (GraphStyle::Bar, _, Some(point)) => {
+                        Line::new(Point::new(pos_x, bottom), point)
+                            .into_styled(line_style)
+                            .draw(target)
+                            .map_err(RenderError::Draw)?;
+                    }
+                    // Gap
+                    (_, _, None) => (),
+                }
+                prev = point;
+                pos_x += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn draw_alarm_log<D>(
+        &mut self,
+        target: &mut D,
+        data: &AlarmLog,
+    ) -> Result<(), RenderError<D::Error>>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let dh = HEIGHT / 5;
+        let text_style = MonoTextStyleBuilder::new()
+            .font(&ALARM_LOG_FONT)
+            .text_color(BinaryColor::On)
+            .build();
+
+        draw_text(
+            target,
+            "ALARMS",
             text_style,
+            Point::zero(),
+            Alignment::Left,
             Baseline::Top,
-        )
-        .draw(&mut self.drv)?;
+        )?;
 
-        self.drv.flush()?;
+        // At most 21 characters, "HH:MM KKK S dSYM vvvv"
+        for (row, logged) in data.newest_first().take(4).enumerate() {
+            let time = logged.datetime.time();
+            let event = &logged.event;
+            self.line_buf.clear();
+            write!(
+                &mut self.line_buf,
+                "{:02}:{:02} {:<3} {}",
+                time.hour(),
+                time.minute(),
+                event.kind,
+                event.severity
+            )?;
+            if let Some(rule) = &event.rule {
+                let rate = rule.rate_window.is_some();
+                write!(
+                    &mut self.line_buf,
+                    " {}{}",
+                    if rate { "d" } else { "" },
+                    rule.channel.symbol()
+                )?;
+                if let Some(value) = event.value {
+                    // Changes scale without the offset
+                    let value = if rate {
+                        self.units.convert_difference(rule.channel, value)
+                    } else {
+                        self.units.convert(rule.channel, value)
+                    };
+                    self.line_buf.push(' ').map_err(|_| fmt::Error)?;
+                    write_compact(&mut self.line_buf, value)?;
+                }
+            }
+            draw_text(
+                target,
+                &self.line_buf,
+                text_style,
+                Point::new(0, (row as i32 + 1) * dh),
+                Alignment::Left,
+                Baseline::Top,
+            )?;
+        }
 
         Ok(())
     }
 
-    fn draw_system_status(&mut self, data: &SystemStatus) -> Result<(), DisplayError> {
-        let dh = (DispSize::HEIGHT / 4) as i32;
+    fn draw_system_status<D>(
+        &mut self,
+        target: &mut D,
+        data: &SystemStatus,
+    ) -> Result<(), RenderError<D::Error>>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let dh = HEIGHT / 5;
         let text_style = MonoTextStyleBuilder::new()
             .font(&SYS_STATS_FONT)
             .text_color(BinaryColor::On)
             .build();
 
-        self.drv.clear();
-
+        // Uptime as days and hours after the first day, at most 12
+        // characters, leaving room for the warm-up countdown
+        let uptime = data.uptime_sec;
+        let (days, hours) = (uptime / 86400, (uptime % 86400) / 3600);
         self.line_buf.clear();
-        write!(
-            &mut self.line_buf,
-            "UT {}",
-            data.uptime_sec.clamp(0, 999999999)
-        )
-        .map_err(|_| DisplayError::InvalidFormatError)?;
-        Text::with_baseline(
-            self.line_buf.as_str(),
-            Point::zero(),
+        if days != 0 {
+            write!(&mut self.line_buf, "UT {}d{:02}h", days, hours)?;
+        } else {
+            write!(
+                &mut self.line_buf,
+                "UT {:02}:{:02}:{:02}",
+                hours,
+                (uptime % 3600) / 60,
+                uptime % 60
+            )?;
+        }
+        if data.warm_up_remaining_sec != 0 {
+            // Warm-up countdown
+            write!(
+                &mut self.line_buf,
+                " WU {}:{:02}",
+                (data.warm_up_remaining_sec / 60).clamp(0, 99),
+                data.warm_up_remaining_sec % 60
+            )?;
+        }
+        draw_text(
+            target,
+            &self.line_buf,
             text_style,
+            Point::zero(),
+            Alignment::Left,
             Baseline::Top,
-        )
-        .draw(&mut self.drv)?;
+        )?;
 
         self.line_buf.clear();
         write!(
             &mut self.line_buf,
             "CNT {}",
             data.record_count.clamp(0, 99999999)
-        )
-        .map_err(|_| DisplayError::InvalidFormatError)?;
-        Text::with_baseline(
-            self.line_buf.as_str(),
-            Point::new(0, dh),
+        )?;
+        draw_text(
+            target,
+            &self.line_buf,
             text_style,
+            Point::new(0, dh),
+            Alignment::Left,
             Baseline::Top,
-        )
-        .draw(&mut self.drv)?;
+        )?;
 
         self.line_buf.clear();
         write!(
@@ -263,15 +1204,15 @@ where
             "ALRM {}  CON {}",
             data.alarm,
             util::DisplayBool::from(data.storage_connected)
-        )
-        .map_err(|_| DisplayError::InvalidFormatError)?;
-        Text::with_baseline(
-            self.line_buf.as_str(),
-            Point::new(0, 2 * dh),
+        )?;
+        draw_text(
+            target,
+            &self.line_buf,
             text_style,
+            Point::new(0, 2 * dh),
+            Alignment::Left,
             Baseline::Top,
-        )
-        .draw(&mut self.drv)?;
+        )?;
 
         self.line_buf.clear();
         write!(
@@ -279,18 +1220,1041 @@ where
             "FULL {}  ERR {}",
             util::DisplayBool::from(data.storage_full),
             util::DisplayBool::from(data.storage_error)
-        )
-        .map_err(|_| DisplayError::InvalidFormatError)?;
-        Text::with_baseline(
-            self.line_buf.as_str(),
+        )?;
+        draw_text(
+            target,
+            &self.line_buf,
+            text_style,
             Point::new(0, 3 * dh),
+            Alignment::Left,
+            Baseline::Top,
+        )?;
+
+        self.line_buf.clear();
+        write!(
+            &mut self.line_buf,
+            "SNS {}  SERR {}",
+            data.sensor_health,
+            data.sensor_errors.clamp(0, 99999)
+        )?;
+        draw_text(
+            target,
+            &self.line_buf,
+            text_style,
+            Point::new(0, 4 * dh),
+            Alignment::Left,
+            Baseline::Top,
+        )
+    }
+
+    /// Blinks, inverted on every other draw
+    fn draw_notification<D>(
+        &mut self,
+        target: &mut D,
+        data: &Notification,
+    ) -> Result<(), RenderError<D::Error>>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        let units = self.units;
+        let dh = HEIGHT / 5;
+        self.inverted = !self.inverted;
+        let (fg, bg) = if self.inverted {
+            (BinaryColor::Off, BinaryColor::On)
+        } else {
+            (BinaryColor::On, BinaryColor::Off)
+        };
+        // The whole display, not only the shifted area
+        target.clear(bg).map_err(RenderError::Draw)?;
+        let text_style = MonoTextStyleBuilder::new()
+            .font(&NOTIFICATION_FONT)
+            .text_color(fg)
+            .build();
+
+        // Each row at most 21 characters
+        let mut rows: [String<LINE_BUF_CAP>; 5] = Default::default();
+        let time = data.datetime.time();
+        write!(
+            &mut rows[0],
+            "! {} {:02}:{:02}:{:02}",
+            data.cause.priority().name(),
+            time.hour(),
+            time.minute(),
+            time.second()
+        )?;
+        match data.cause {
+            Cause::Alarm { rule, value } => {
+                let channel = rule.channel;
+                let unit = units.symbol(channel);
+                // Changes scale without the offset
+                let convert = |v: f32| {
+                    if rule.rate_window.is_some() {
+                        units.convert_difference(channel, v)
+                    } else {
+                        units.convert(channel, v)
+                    }
+                };
+                write!(&mut rows[1], "{}", channel.name())?;
+
+                write!(&mut rows[2], "Value ")?;
+                match value {
+                    Some(value) => write_compact(&mut rows[2], convert(value))?,
+                    None => write!(&mut rows[2], "---")?,
+                }
+                write!(&mut rows[2], " {}", unit)?;
+                if let Some(window) = rule.rate_window {
+                    write!(&mut rows[2], "/{}m", window.0.clamp(0, 999))?;
+                }
+
+                write!(&mut rows[3], "Limit ")?;
+                match rule.comparison {
+                    Comparison::Above(threshold) => {
+                        write!(&mut rows[3], "> ")?;
+                        write_compact(&mut rows[3], convert(threshold))?;
+                    }
+                    Comparison::Below(threshold) => {
+                        write!(&mut rows[3], "< ")?;
+                        write_compact(&mut rows[3], convert(threshold))?;
+                    }
+                    Comparison::Outside { low, high } => {
+                        write_compact(&mut rows[3], convert(low))?;
+                        write!(&mut rows[3], "..")?;
+                        write_compact(&mut rows[3], convert(high))?;
+                    }
+                }
+                write!(&mut rows[3], " {}", unit)?;
+            }
+            Cause::SensorFailure => {
+                write!(&mut rows[1], "Sensor failed")?;
+                write!(&mut rows[2], "Relays are off")?;
+            }
+            Cause::StorageError => {
+                write!(&mut rows[1], "SD card error")?;
+                write!(&mut rows[2], "Records not saved")?;
+            }
+            Cause::StorageFull => {
+                write!(&mut rows[1], "SD card full")?;
+                write!(&mut rows[2], "Records not saved")?;
+            }
+        }
+        write!(&mut rows[4], "Press to dismiss")?;
+
+        for (row, line) in rows.iter().enumerate() {
+            draw_text(
+                target,
+                line,
+                text_style,
+                Point::new(0, row as i32 * dh),
+                Alignment::Left,
+                Baseline::Top,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// The label on the left and the line buffer right-aligned on the row
+    fn draw_label_value<D>(
+        &self,
+        target: &mut D,
+        text_style: MonoTextStyle<'_, BinaryColor>,
+        label: &str,
+        pos_y: i32,
+    ) -> Result<(), RenderError<D::Error>>
+    where
+        D: DrawTarget<Color = BinaryColor>,
+    {
+        draw_text(
+            target,
+            label,
+            text_style,
+            Point::new(0, pos_y),
+            Alignment::Left,
+            Baseline::Top,
+        )?;
+        draw_text(
+            target,
+            &self.line_buf,
             text_style,
+            Point::new(WIDTH, pos_y),
+            Alignment::Right,
             Baseline::Top,
         )
-        .draw(&mut self.drv)?;
+    }
+}
+
+impl Default for Renderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The target the views draw onto, whatever the renderer was given, so the
+/// views can be plain function pointers. Drawing is moved by the pixel
+/// shift offset, clearing covers the whole target.
+pub struct Frame<'a> {
+    canvas: &'a mut dyn Canvas,
+    offset: Point,
+}
+
+impl Dimensions for Frame<'_> {
+    fn bounding_box(&self) -> Rectangle {
+        self.canvas.bounding_box().translate(-self.offset)
+    }
+}
 
-        self.drv.flush()?;
+impl DrawTarget for Frame<'_> {
+    type Color = BinaryColor;
+    /// Target errors are returned by render
+    type Error = Infallible;
 
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<BinaryColor>>,
+    {
+        let offset = self.offset;
+        self.canvas.draw_pixels(
+            &mut pixels
+                .into_iter()
+                .map(|Pixel(point, color)| Pixel(point + offset, color)),
+        );
         Ok(())
     }
+
+    /// Not moved, so no edge is left uncleared
+    fn clear(&mut self, color: BinaryColor) -> Result<(), Self::Error> {
+        let area = self.canvas.bounding_box();
+        self.canvas
+            .draw_pixels(&mut area.points().map(|point| Pixel(point, color)));
+        Ok(())
+    }
+}
+
+/// An object safe DrawTarget
+trait Canvas {
+    fn bounding_box(&self) -> Rectangle;
+
+    fn draw_pixels(&mut self, pixels: &mut dyn Iterator<Item = Pixel<BinaryColor>>);
+}
+
+/// Keeps the first error, nothing more is drawn after it
+struct ErasedTarget<'a, D: DrawTarget> {
+    target: &'a mut D,
+    error: Option<D::Error>,
+}
+
+impl<D> Canvas for ErasedTarget<'_, D>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    fn bounding_box(&self) -> Rectangle {
+        self.target.bounding_box()
+    }
+
+    fn draw_pixels(&mut self, pixels: &mut dyn Iterator<Item = Pixel<BinaryColor>>) {
+        if self.error.is_none() {
+            if let Err(e) = self.target.draw_iter(pixels) {
+                self.error.replace(e);
+            }
+        }
+    }
+}
+
+fn render_dashboard(
+    renderer: &mut Renderer,
+    frame: &mut Frame,
+    context: &ViewContext,
+) -> Result<bool, RenderError<Infallible>> {
+    renderer.draw_dashboard(frame, context)?;
+    Ok(true)
+}
+
+fn render_time(
+    renderer: &mut Renderer,
+    frame: &mut Frame,
+    context: &ViewContext,
+) -> Result<bool, RenderError<Infallible>> {
+    renderer.draw_time(frame, context.datetime)?;
+    Ok(true)
+}
+
+fn render_date(
+    renderer: &mut Renderer,
+    frame: &mut Frame,
+    context: &ViewContext,
+) -> Result<bool, RenderError<Infallible>> {
+    renderer.draw_date(frame, &context.datetime.date())?;
+    Ok(true)
+}
+
+fn render_sensor_readings(
+    renderer: &mut Renderer,
+    frame: &mut Frame,
+    context: &ViewContext,
+) -> Result<bool, RenderError<Infallible>> {
+    match context.sensor {
+        Some(sensor) => renderer.draw_sensor_readings(frame, sensor)?,
+        None => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn render_psychrometrics(
+    renderer: &mut Renderer,
+    frame: &mut Frame,
+    context: &ViewContext,
+) -> Result<bool, RenderError<Infallible>> {
+    match context.sensor {
+        Some(sensor) => renderer.draw_psychrometrics(frame, &Psychrometrics::from(sensor))?,
+        None => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn render_barometer(
+    renderer: &mut Renderer,
+    frame: &mut Frame,
+    context: &ViewContext,
+) -> Result<bool, RenderError<Infallible>> {
+    if context.barometer.pressure().is_none() {
+        return Ok(false);
+    }
+    renderer.draw_barometer(frame, context.barometer)?;
+    Ok(true)
+}
+
+fn render_daily_extremes(
+    renderer: &mut Renderer,
+    frame: &mut Frame,
+    context: &ViewContext,
+) -> Result<bool, RenderError<Infallible>> {
+    if context.daily_extremes.is_empty() {
+        return Ok(false);
+    }
+    renderer.draw_daily_extremes(frame, context.daily_extremes)?;
+    Ok(true)
+}
+
+fn render_graph<const INDEX: usize>(
+    renderer: &mut Renderer,
+    frame: &mut Frame,
+    context: &ViewContext,
+) -> Result<bool, RenderError<Infallible>> {
+    match context.history.get(INDEX) {
+        Some(history) if !history.is_empty() => renderer.draw_graph(frame, history)?,
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn render_alarm_log(
+    renderer: &mut Renderer,
+    frame: &mut Frame,
+    context: &ViewContext,
+) -> Result<bool, RenderError<Infallible>> {
+    if context.alarm_log.is_empty() {
+        return Ok(false);
+    }
+    renderer.draw_alarm_log(frame, context.alarm_log)?;
+    Ok(true)
+}
+
+fn render_system_status(
+    renderer: &mut Renderer,
+    frame: &mut Frame,
+    context: &ViewContext,
+) -> Result<bool, RenderError<Infallible>> {
+    renderer.draw_system_status(frame, context.status)?;
+    Ok(true)
+}
+
+fn render_notification(
+    renderer: &mut Renderer,
+    frame: &mut Frame,
+    context: &ViewContext,
+) -> Result<bool, RenderError<Infallible>> {
+    match context.notification {
+        Some(notification) => renderer.draw_notification(frame, notification)?,
+        None => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn draw_text<D>(
+    target: &mut D,
+    text: &str,
+    character_style: MonoTextStyle<'_, BinaryColor>,
+    position: Point,
+    alignment: Alignment,
+    baseline: Baseline,
+) -> Result<(), RenderError<D::Error>>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let text_style = TextStyleBuilder::new()
+        .alignment(alignment)
+        .baseline(baseline)
+        .build();
+    Text::with_text_style(text, position, character_style, text_style)
+        .draw(target)
+        .map_err(RenderError::Draw)?;
+    Ok(())
+}
+
+/// Filled arrow pointing up, down or right within size at pos
+fn draw_trend_arrow<D>(
+    target: &mut D,
+    trend: Trend,
+    pos: Point,
+    size: Size,
+) -> Result<(), RenderError<D::Error>>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let (x, y) = (pos.x, pos.y);
+    let (w, h) = (size.width as i32, size.height as i32);
+    let arrow = match trend {
+        Trend::Rising => Triangle::new(
+            Point::new(x + w / 2, y),
+            Point::new(x, y + h),
+            Point::new(x + w, y + h),
+        ),
+        Trend::Falling => Triangle::new(
+            Point::new(x, y),
+            Point::new(x + w, y),
+            Point::new(x + w / 2, y + h),
+        ),
+        Trend::Steady => Triangle::new(
+            Point::new(x, y),
+            Point::new(x, y + h),
+            Point::new(x + w, y + h / 2),
+        ),
+    };
+    arrow
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(target)
+        .map_err(RenderError::Draw)
+}
+
+/// At most 4 characters for narrow columns, whole numbers from 10 and
+/// thousands with a k suffix from 10000
+fn write_compact(buf: &mut String<LINE_BUF_CAP>, value: f32) -> fmt::Result {
+    let value = value.clamp(-999.0, 999_000.0);
+    if value.abs() < 9.95 {
+        write!(buf, "{:.1}", value)
+    } else if value < 9999.5 {
+        write!(buf, "{:.0}", value)
+    } else {
+        write!(buf, "{:.0}k", value / 1000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alarm::{AlarmEvent, AlarmRule, EventKind, Severity};
+    use crate::units::{GasResistanceUnit, TemperatureUnit};
+    use crate::warm_up::{WarmUp, WarmUpConfig};
+    use embedded_time::duration::Seconds;
+
+    extern crate std;
+    use std::string::String as StdString;
+    use std::{env, fs, path::Path};
+
+    const SETTLED: WarmUpConfig = WarmUpConfig {
+        temperature: Seconds(0),
+        humidity: Seconds(0),
+        pressure: Seconds(0),
+        gas_resistance: Seconds(0),
+    };
+
+    const UNITS: Units = Units {
+        temperature: TemperatureUnit::Celsius,
+        pressure: PressureUnit::Hectopascal,
+        gas_resistance: GasResistanceUnit::Ohm,
+    };
+
+    /// The 128x64 display, pixels drawn past its edges are counted
+    struct TestFrame {
+        pixels: [[bool; WIDTH as usize]; HEIGHT as usize],
+        outside: usize,
+    }
+
+    impl TestFrame {
+        fn new() -> Self {
+            TestFrame {
+                pixels: [[false; WIDTH as usize]; HEIGHT as usize],
+                outside: 0,
+            }
+        }
+
+        /// A line per row, '#' for lit pixels
+        fn to_ascii(&self) -> StdString {
+            let mut ascii = StdString::new();
+            for row in self.pixels.iter() {
+                ascii.extend(row.iter().map(|&on| if on { '#' } else { '.' }));
+                ascii.push('\n');
+            }
+            ascii
+        }
+    }
+
+    impl OriginDimensions for TestFrame {
+        fn size(&self) -> Size {
+            Size::new(WIDTH as u32, HEIGHT as u32)
+        }
+    }
+
+    impl DrawTarget for TestFrame {
+        type Color = BinaryColor;
+        type Error = Infallible;
+
+        fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+        where
+            I: IntoIterator<Item = Pixel<BinaryColor>>,
+        {
+            for Pixel(point, color) in pixels {
+                if (0..WIDTH).contains(&point.x) && (0..HEIGHT).contains(&point.y) {
+                    self.pixels[point.y as usize][point.x as usize] = color.is_on();
+                } else {
+                    self.outside += 1;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Compares against snapshots/display/<name>.txt, a missing snapshot
+    /// fails. With UPDATE_SNAPSHOTS set the snapshot is written instead.
+    fn assert_snapshot(name: &str, frame: &TestFrame) {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("snapshots/display");
+        let path = dir.join(name).with_extension("txt");
+        let actual = frame.to_ascii();
+        if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(&path, actual).unwrap();
+            return;
+        }
+        match fs::read_to_string(&path) {
+            Ok(expected) => assert!(
+                expected == actual,
+                "{} differs from {}, rerun with UPDATE_SNAPSHOTS=1 if intended:\n{}",
+                name,
+                path.display(),
+                actual
+            ),
+            Err(e) => panic!(
+                "{} snapshot {}: {}, rerun with UPDATE_SNAPSHOTS=1 to write it:\n{}",
+                name,
+                path.display(),
+                e,
+                actual
+            ),
+        }
+    }
+
+    fn at(sec: u32) -> Instant<SystemClock> {
+        Instant::new(sec)
+    }
+
+    fn datetime() -> NaiveDateTime {
+        NaiveDate::from_ymd(2021, 6, 15).and_hms(14, 30, 5)
+    }
+
+    fn measurement(temperature: f32) -> Measurement {
+        Measurement {
+            temperature,
+            humidity: 50.0,
+            pressure: 1013.25,
+            gas_resistance: Some(52_400),
+        }
+    }
+
+    /// Three hours of samples rising to the pressure
+    fn barometer(pressure: f32) -> Barometer {
+        let mut barometer = Barometer::new();
+        for i in 0..19 {
+            barometer.update(&at(i * 600), pressure - 1.8 + 0.1 * i as f32);
+        }
+        barometer
+    }
+
+    fn rule(s: &str) -> AlarmRule {
+        AlarmRule::parse(s).unwrap()
+    }
+
+    /// Everything the views show on a typical day
+    struct Fixture {
+        datetime: NaiveDateTime,
+        sensor: Measurement,
+        barometer: Barometer,
+        daily_extremes: DailyExtremes,
+        history: History,
+        alarm_log: AlarmLog,
+        status: SystemStatus,
+        notification: Notification,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            let datetime = datetime();
+            let warm_up = WarmUp::new(SETTLED, &at(0));
+
+            let mut daily_extremes = DailyExtremes::new();
+            daily_extremes.roll_over(&datetime.date());
+            for (hour, temperature, humidity) in [(6, 12.3, 81.0), (14, 27.8, 38.0)] {
+                let data = Measurement {
+                    humidity,
+                    ..measurement(temperature)
+                };
+                let dt = datetime.date().and_hms(hour, 10, 0);
+                daily_extremes.update(&dt, &at(0), &data, &warm_up);
+            }
+
+            // A sawtooth over the whole span
+            let mut history = History::new(config::GRAPHS);
+            for i in 0..300 {
+                let data = Measurement {
+                    pressure: 1005.0 + (i % 50) as f32 * 0.2,
+                    ..measurement(18.0 + (i % 60) as f32 * 0.1)
+                };
+                history.update(&at(i * 75), &data, &warm_up);
+            }
+
+            let raised = rule("temperature above 30 critical");
+            let mut alarm_log = AlarmLog::new();
+            let events = [
+                (
+                    EventKind::Raised,
+                    Severity::Critical,
+                    Some(raised),
+                    Some(31.2),
+                ),
+                (EventKind::Acknowledged, Severity::Critical, None, None),
+                (
+                    EventKind::Cleared,
+                    Severity::Critical,
+                    Some(raised),
+                    Some(33.9),
+                ),
+                (
+                    EventKind::Raised,
+                    Severity::Warning,
+                    Some(rule("pressure below -2 warning rate 60")),
+                    Some(-2.6),
+                ),
+            ];
+            for (minute, (kind, severity, rule, value)) in events.iter().enumerate() {
+                let event = AlarmEvent {
+                    kind: *kind,
+                    severity: *severity,
+                    rule: *rule,
+                    value: *value,
+                };
+                alarm_log.push(&datetime.date().and_hms(13, minute as u32 * 7, 0), &event);
+            }
+
+            Fixture {
+                datetime,
+                sensor: measurement(23.4),
+                barometer: barometer(1013.2),
+                daily_extremes,
+                history,
+                alarm_log,
+                status: SystemStatus {
+                    uptime_sec: 3723,
+                    alarm: AlarmStatus::On(Severity::Warning),
+                    warm_up_remaining_sec: 0,
+                    record_count: 1234,
+                    storage_connected: true,
+                    storage_full: false,
+                    storage_error: false,
+                    sensor_health: SensorHealth::Ok,
+                    sensor_errors: 2,
+                },
+                notification: Notification {
+                    cause: Cause::Alarm {
+                        rule: raised,
+                        value: Some(31.2),
+                    },
+                    datetime,
+                },
+            }
+        }
+
+        /// The widest values each view shows
+        fn extremes() -> Self {
+            Fixture {
+                sensor: Measurement {
+                    temperature: -40.0,
+                    humidity: 100.0,
+                    pressure: 9999.0,
+                    gas_resistance: Some(999_999),
+                },
+                barometer: barometer(9999.0),
+                status: SystemStatus {
+                    // 100 days
+                    uptime_sec: 100 * 86400 + 5 * 3600 + 7,
+                    alarm: AlarmStatus::Snoozed(Severity::Critical),
+                    warm_up_remaining_sec: 5999,
+                    record_count: 99_999_999,
+                    storage_connected: true,
+                    storage_full: true,
+                    storage_error: true,
+                    sensor_health: SensorHealth::Degraded,
+                    sensor_errors: 99_999,
+                },
+                ..Fixture::new()
+            }
+        }
+
+        fn context(&self) -> ViewContext<'_> {
+            ViewContext {
+                datetime: &self.datetime,
+                sensor: Some(&self.sensor),
+                barometer: &self.barometer,
+                daily_extremes: &self.daily_extremes,
+                history: &self.history,
+                alarm_log: &self.alarm_log,
+                status: &self.status,
+                notification: Some(&self.notification),
+            }
+        }
+    }
+
+    fn renderer() -> Renderer {
+        let mut renderer = Renderer::new();
+        renderer.set_units(UNITS);
+        renderer.set_time_format(TimeFormat {
+            clock: ClockFormat::TwentyFourHour,
+            seconds: false,
+            date: false,
+        });
+        renderer
+    }
+
+    /// Renders the view within the frame and compares it to its snapshot
+    fn assert_view(name: &str, renderer: &mut Renderer, view: &View, fixture: &Fixture) {
+        let mut frame = TestFrame::new();
+        assert!(renderer
+            .render(&mut frame, Point::zero(), view, &fixture.context())
+            .unwrap());
+        assert_eq!(frame.outside, 0, "{} drawn past the display", name);
+        assert!(
+            frame.pixels.iter().flatten().any(|&on| on),
+            "{} is blank",
+            name
+        );
+        assert_snapshot(name, &frame);
+    }
+
+    #[test]
+    fn dashboard() {
+        assert_view(
+            "dashboard",
+            &mut renderer(),
+            &View::DASHBOARD,
+            &Fixture::new(),
+        );
+    }
+
+    #[test]
+    fn dashboard_extremes() {
+        let fixture = Fixture {
+            sensor: Measurement {
+                temperature: 999.9,
+                ..Fixture::extremes().sensor
+            },
+            ..Fixture::extremes()
+        };
+        assert_view(
+            "dashboard_extremes",
+            &mut renderer(),
+            &View::DASHBOARD,
+            &fixture,
+        );
+    }
+
+    #[test]
+    fn time() {
+        assert_view("time", &mut renderer(), &View::TIME, &Fixture::new());
+    }
+
+    #[test]
+    fn time_formats() {
+        let fixture = Fixture::new();
+        let dh = TIME_FONT.character_size.height as i32;
+        for clock in ClockFormat::ALL {
+            for seconds in [false, true] {
+                for date in [false, true] {
+                    let mut renderer = renderer();
+                    renderer.set_time_format(TimeFormat {
+                        clock,
+                        seconds,
+                        date,
+                    });
+                    let mut frame = TestFrame::new();
+                    assert!(renderer
+                        .render(&mut frame, Point::zero(), &View::TIME, &fixture.context())
+                        .unwrap());
+                    let name = std::format!(
+                        "time_{}{}{}",
+                        clock.name(),
+                        if seconds { "_seconds" } else { "" },
+                        if date { "_date" } else { "" }
+                    );
+                    // "hh:mm:ss" takes the full width
+                    assert_eq!(frame.outside, 0, "{} drawn past the display", name);
+
+                    // The AM/PM marker and the date are on a line under the
+                    // time's character cells
+                    let has_detail = clock == ClockFormat::TwelveHour || date;
+                    let detail_dh = TIME_DETAIL_FONT.character_size.height as i32 + 2;
+                    let pos_y = if has_detail {
+                        (HEIGHT - dh - detail_dh) / 2
+                    } else {
+                        (HEIGHT - dh) / 2
+                    };
+                    let lit_below = frame.pixels[(pos_y + dh) as usize..]
+                        .iter()
+                        .flatten()
+                        .any(|&on| on);
+                    assert_eq!(lit_below, has_detail, "{}", name);
+                    assert_snapshot(&name, &frame);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn time_with_seconds_spans_the_width() {
+        let mut renderer = renderer();
+        renderer.set_time_format(TimeFormat {
+            clock: ClockFormat::TwentyFourHour,
+            seconds: true,
+            date: false,
+        });
+        let fixture = Fixture {
+            datetime: NaiveDate::from_ymd(2021, 6, 15).and_hms(23, 59, 59),
+            ..Fixture::new()
+        };
+        let mut frame = TestFrame::new();
+        assert!(renderer
+            .render(&mut frame, Point::zero(), &View::TIME, &fixture.context())
+            .unwrap());
+        assert_eq!(frame.outside, 0);
+        let lit_columns: std::vec::Vec<usize> = (0..WIDTH as usize)
+            .filter(|&x| frame.pixels.iter().any(|row| row[x]))
+            .collect();
+        // Within a character cell of either edge
+        let char_width = (TIME_FONT.character_size.width + TIME_FONT.character_spacing) as usize;
+        assert!(lit_columns[0] < char_width);
+        assert!(lit_columns[lit_columns.len() - 1] >= WIDTH as usize - char_width);
+    }
+
+    #[test]
+    fn date() {
+        assert_view("date", &mut renderer(), &View::DATE, &Fixture::new());
+    }
+
+    #[test]
+    fn sensor_readings() {
+        let fixture = Fixture::new();
+        assert_view(
+            "sensor_readings",
+            &mut renderer(),
+            &View::SENSOR_READINGS,
+            &fixture,
+        );
+    }
+
+    #[test]
+    fn sensor_readings_extremes() {
+        let fixture = Fixture::extremes();
+        let view = &View::SENSOR_READINGS;
+        assert_view("sensor_readings_extremes", &mut renderer(), view, &fixture);
+    }
+
+    #[test]
+    fn psychrometrics() {
+        let fixture = Fixture::new();
+        assert_view(
+            "psychrometrics",
+            &mut renderer(),
+            &View::PSYCHROMETRICS,
+            &fixture,
+        );
+    }
+
+    #[test]
+    fn barometer_view() {
+        assert_view(
+            "barometer",
+            &mut renderer(),
+            &View::BAROMETER,
+            &Fixture::new(),
+        );
+    }
+
+    #[test]
+    fn barometer_extremes() {
+        let fixture = Fixture::extremes();
+        assert_view(
+            "barometer_extremes",
+            &mut renderer(),
+            &View::BAROMETER,
+            &fixture,
+        );
+    }
+
+    #[test]
+    fn daily_extremes() {
+        let fixture = Fixture::new();
+        assert_view(
+            "daily_extremes",
+            &mut renderer(),
+            &View::DAILY_EXTREMES,
+            &fixture,
+        );
+    }
+
+    #[test]
+    fn graphs() {
+        let fixture = Fixture::new();
+        let mut renderer = renderer();
+        assert_view("graph_line", &mut renderer, &View::graph::<0>(), &fixture);
+        assert_view("graph_bar", &mut renderer, &View::graph::<2>(), &fixture);
+    }
+
+    #[test]
+    fn alarm_log() {
+        assert_view(
+            "alarm_log",
+            &mut renderer(),
+            &View::ALARM_LOG,
+            &Fixture::new(),
+        );
+    }
+
+    #[test]
+    fn system_status() {
+        let fixture = Fixture::new();
+        assert_view(
+            "system_status",
+            &mut renderer(),
+            &View::SYSTEM_STATUS,
+            &fixture,
+        );
+    }
+
+    #[test]
+    fn system_status_extremes() {
+        let fixture = Fixture::extremes();
+        let view = &View::SYSTEM_STATUS;
+        assert_view("system_status_extremes", &mut renderer(), view, &fixture);
+    }
+
+    #[test]
+    fn notification() {
+        let fixture = Fixture::new();
+        let mut renderer = renderer();
+        // Blinks, inverted first
+        let view = &View::NOTIFICATION;
+        assert_view("notification_inverted", &mut renderer, view, &fixture);
+        assert_view("notification", &mut renderer, view, &fixture);
+    }
+
+    #[test]
+    fn shifted_notification_fills_the_display() {
+        let fixture = Fixture::new();
+        let (last_x, last_y) = (WIDTH as usize - 1, HEIGHT as usize - 1);
+        // The edge the content moved away from
+        let edges = [
+            (Point::new(1, 1), 0, 0),
+            (Point::new(-1, -1), last_x, last_y),
+        ];
+        for (offset, x, y) in edges {
+            let mut renderer = renderer();
+            let mut frame = TestFrame::new();
+            assert!(renderer
+                .render(&mut frame, offset, &View::NOTIFICATION, &fixture.context())
+                .unwrap());
+            // Inverted on the first draw
+            assert!(frame.pixels[y].iter().all(|&on| on), "{:?}", offset);
+            assert!(frame.pixels.iter().all(|row| row[x]), "{:?}", offset);
+        }
+    }
+
+    fn render(view: &View, fixture: &Fixture) -> TestFrame {
+        let mut frame = TestFrame::new();
+        renderer()
+            .render(&mut frame, Point::zero(), view, &fixture.context())
+            .unwrap();
+        frame
+    }
+
+    #[test]
+    fn views_follow_their_data() {
+        let typical = Fixture::new();
+        let extremes = Fixture::extremes();
+        for view in [
+            View::DASHBOARD,
+            View::SENSOR_READINGS,
+            View::PSYCHROMETRICS,
+            View::BAROMETER,
+            View::SYSTEM_STATUS,
+        ] {
+            assert!(
+                render(&view, &typical).pixels != render(&view, &extremes).pixels,
+                "{}",
+                view.name
+            );
+        }
+        let later = Fixture {
+            datetime: NaiveDate::from_ymd(2021, 7, 16).and_hms(15, 41, 5),
+            ..Fixture::new()
+        };
+        for view in [View::TIME, View::DATE] {
+            assert!(
+                render(&view, &typical).pixels != render(&view, &later).pixels,
+                "{}",
+                view.name
+            );
+        }
+    }
+
+    #[test]
+    fn views_without_data_are_not_shown() {
+        let fixture = Fixture {
+            daily_extremes: DailyExtremes::new(),
+            history: History::new(config::GRAPHS),
+            alarm_log: AlarmLog::new(),
+            barometer: Barometer::new(),
+            ..Fixture::new()
+        };
+        let context = ViewContext {
+            sensor: None,
+            notification: None,
+            ..fixture.context()
+        };
+        let mut renderer = renderer();
+        for view in [
+            View::SENSOR_READINGS,
+            View::PSYCHROMETRICS,
+            View::BAROMETER,
+            View::DAILY_EXTREMES,
+            View::graph::<0>(),
+            View::ALARM_LOG,
+            View::NOTIFICATION,
+        ] {
+            let mut frame = TestFrame::new();
+            assert!(
+                !renderer
+                    .render(&mut frame, Point::zero(), &view, &context)
+                    .unwrap(),
+                "{}",
+                view.name
+            );
+        }
+    }
 }
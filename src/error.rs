@@ -1,7 +1,7 @@
 //! Top level error
 
 use crate::hal::{i2c, serial};
-use crate::record;
+use environment_logger::record;
 
 #[derive(Debug, err_derive::Error)]
 pub enum Error {
@@ -17,9 +17,6 @@ pub enum Error {
     #[error(display = "RTC error")]
     Rtc(#[error(source)] ds323x::Error<i2c::Error, ()>),
 
-    #[error(display = "Sensor error")]
-    Sensor(#[error(source)] bme680::Error<i2c::Error, i2c::Error>),
-
     #[error(display = "Record error")]
     Record(#[error(source)] record::Error),
 
@@ -1,11 +1,14 @@
-use crate::hal::hal::{digital::v2::OutputPin, spi::FullDuplex};
 use crate::system_clock::SystemClock;
+use embedded_hal::{digital::v2::OutputPin, spi::FullDuplex};
 use embedded_sdmmc::{Controller, Error, Mode, SdMmcError, SdMmcSpi, TimeSource, VolumeIdx};
 use embedded_time::{duration::Minutes, Instant};
 
 const WRITE_INTERVAL: Minutes = Minutes(15_u32);
 const VOLUME_IDX: VolumeIdx = VolumeIdx(0);
-const FILENAME: &str = "RECORDS.CSV";
+const RECORDS_FILENAME: &str = "RECORDS.CSV";
+const SETTINGS_FILENAME: &str = "SETTINGS.CFG";
+const ALARMS_FILENAME: &str = "ALARMS.LOG";
+const DAILY_FILENAME: &str = "DAILY.CSV";
 
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
 struct InitializedStateData {
@@ -60,31 +63,107 @@ where
         self.data.is_some()
     }
 
-    /// Returns true if did write
+    /// Returns true if did write, force writes regardless of the write interval
     pub fn write(
         &mut self,
         now: &Instant<SystemClock>,
         buffer: &[u8],
+        force: bool,
     ) -> Result<bool, Error<SdMmcError>> {
         if let Some(data) = &mut self.data {
             if let Some(dur) = now.checked_duration_since(&data.last_write) {
-                if dur >= WRITE_INTERVAL.into() {
+                if force || dur >= WRITE_INTERVAL.into() {
                     data.last_write = *now;
-                    let mut volume = self.ctrl.get_volume(VOLUME_IDX)?;
-                    let root_dir = self.ctrl.open_root_dir(&volume)?;
-                    let mut file = self.ctrl.open_file_in_dir(
-                        &mut volume,
-                        &root_dir,
-                        FILENAME,
-                        Mode::ReadWriteCreateOrAppend,
-                    )?;
-                    self.ctrl.write(&mut volume, &mut file, buffer)?;
-                    self.ctrl.close_file(&volume, file)?;
-                    self.ctrl.close_dir(&volume, root_dir);
+                    self.append(RECORDS_FILENAME, buffer)?;
                     return Ok(true);
                 }
             }
         }
         Ok(false)
     }
+
+    /// Write immediately, regardless of the write interval
+    pub fn write_header(&mut self, buffer: &[u8]) -> Result<(), Error<SdMmcError>> {
+        if self.data.is_some() {
+            self.append(RECORDS_FILENAME, buffer)?;
+        }
+        Ok(())
+    }
+
+    /// Append to the alarm event log, regardless of the write interval
+    pub fn write_alarm_log(&mut self, buffer: &[u8]) -> Result<(), Error<SdMmcError>> {
+        if self.data.is_some() {
+            self.append(ALARMS_FILENAME, buffer)?;
+        }
+        Ok(())
+    }
+
+    /// Append to the daily summary, regardless of the write interval
+    pub fn write_daily(&mut self, buffer: &[u8]) -> Result<(), Error<SdMmcError>> {
+        if self.data.is_some() {
+            self.append(DAILY_FILENAME, buffer)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the number of bytes read, None if there is no settings file
+    pub fn read_settings(&mut self, buffer: &mut [u8]) -> Result<Option<usize>, Error<SdMmcError>> {
+        if self.data.is_none() {
+            return Ok(None);
+        }
+        let mut volume = self.ctrl.get_volume(VOLUME_IDX)?;
+        let root_dir = self.ctrl.open_root_dir(&volume)?;
+        let mut file = match self.ctrl.open_file_in_dir(
+            &mut volume,
+            &root_dir,
+            SETTINGS_FILENAME,
+            Mode::ReadOnly,
+        ) {
+            Ok(file) => file,
+            Err(Error::FileNotFound) => {
+                self.ctrl.close_dir(&volume, root_dir);
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        };
+        let mut len = 0;
+        while len < buffer.len() && !file.eof() {
+            let n = self.ctrl.read(&volume, &mut file, &mut buffer[len..])?;
+            if n == 0 {
+                break;
+            }
+            len += n;
+        }
+        self.ctrl.close_file(&volume, file)?;
+        self.ctrl.close_dir(&volume, root_dir);
+        Ok(Some(len))
+    }
+
+    pub fn write_settings(&mut self, buffer: &[u8]) -> Result<(), Error<SdMmcError>> {
+        if self.data.is_some() {
+            self.write_file(SETTINGS_FILENAME, buffer, Mode::ReadWriteCreateOrTruncate)?;
+        }
+        Ok(())
+    }
+
+    fn append(&mut self, filename: &str, buffer: &[u8]) -> Result<(), Error<SdMmcError>> {
+        self.write_file(filename, buffer, Mode::ReadWriteCreateOrAppend)
+    }
+
+    fn write_file(
+        &mut self,
+        filename: &str,
+        buffer: &[u8],
+        mode: Mode,
+    ) -> Result<(), Error<SdMmcError>> {
+        let mut volume = self.ctrl.get_volume(VOLUME_IDX)?;
+        let root_dir = self.ctrl.open_root_dir(&volume)?;
+        let mut file = self
+            .ctrl
+            .open_file_in_dir(&mut volume, &root_dir, filename, mode)?;
+        self.ctrl.write(&mut volume, &mut file, buffer)?;
+        self.ctrl.close_file(&volume, file)?;
+        self.ctrl.close_dir(&volume, root_dir);
+        Ok(())
+    }
 }
@@ -0,0 +1,263 @@
+//! Per-channel spike rejection and smoothing between the sensor and its
+//! consumers
+
+use crate::config;
+use crate::ring_buffer::RingBuffer;
+use crate::sensor::Measurement;
+use micromath::F32Ext;
+
+/// Largest supported median window
+pub const MAX_MEDIAN_LEN: usize = 9;
+
+/// A real step change is accepted after this many consecutive rejections
+const MAX_CONSECUTIVE_REJECTIONS: u32 = 3;
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub struct FilterConfig {
+    /// Reject a sample that differs from the previously accepted one by more
+    /// than this, in the channel's units. None disables the check.
+    pub max_step: Option<f32>,
+
+    /// Median of the last N samples, 1 disables (clamped to MAX_MEDIAN_LEN)
+    pub median_len: usize,
+
+    /// Exponential moving average smoothing factor in (0, 1], 1 disables
+    pub ema_alpha: f32,
+}
+
+impl FilterConfig {
+    /// Pass samples through unmodified
+    pub const NONE: Self = FilterConfig {
+        max_step: None,
+        median_len: 1,
+        ema_alpha: 1.0,
+    };
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        FilterConfig::NONE
+    }
+}
+
+pub struct ChannelFilter {
+    config: FilterConfig,
+    last_accepted: Option<f32>,
+    consecutive_rejections: u32,
+    window: RingBuffer<f32, MAX_MEDIAN_LEN>,
+    ema: Option<f32>,
+}
+
+impl ChannelFilter {
+    pub fn new(config: FilterConfig) -> Self {
+        ChannelFilter {
+            config,
+            last_accepted: None,
+            consecutive_rejections: 0,
+            window: RingBuffer::new(),
+            ema: None,
+        }
+    }
+
+    pub fn update(&mut self, sample: f32) -> f32 {
+        // Outlier rejection, an outlier is replaced by the last accepted value
+        let sample = match (self.config.max_step, self.last_accepted) {
+            (Some(max_step), Some(last))
+                if (sample - last).abs() > max_step
+                    && self.consecutive_rejections < MAX_CONSECUTIVE_REJECTIONS =>
+            {
+                self.consecutive_rejections += 1;
+                last
+            }
+            _ => {
+                self.consecutive_rejections = 0;
+                self.last_accepted.replace(sample);
+                sample
+            }
+        };
+
+        // Median of the most recent median_len samples
+        self.window.push(sample);
+        let median_len = self.config.median_len.clamp(1, MAX_MEDIAN_LEN);
+        let count = self.window.len().min(median_len);
+        let mut sorted = [0.0_f32; MAX_MEDIAN_LEN];
+        for (dst, src) in sorted.iter_mut().zip(self.window.iter().rev().take(count)) {
+            *dst = src;
+        }
+        let sorted = &mut sorted[..count];
+        sorted.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(core::cmp::Ordering::Equal));
+        let median = sorted[count / 2];
+
+        // Exponential moving average
+        let alpha = self.config.ema_alpha.clamp(0.0, 1.0);
+        let smoothed = match self.ema {
+            Some(prev) => prev + (alpha * (median - prev)),
+            None => median,
+        };
+        self.ema.replace(smoothed);
+        smoothed
+    }
+
+    /// Start over, the next sample is passed through as is
+    pub fn reset(&mut self) {
+        *self = ChannelFilter::new(self.config);
+    }
+}
+
+/// Filter input and output of the same sample
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Readings {
+    /// After calibration and self-heating compensation, not the driver value
+    pub unfiltered: Measurement,
+    pub filtered: Measurement,
+}
+
+impl Readings {
+    /// The values selected by config::RECORD_UNFILTERED_VALUES
+    pub fn recorded(&self) -> &Measurement {
+        if config::RECORD_UNFILTERED_VALUES {
+            &self.unfiltered
+        } else {
+            &self.filtered
+        }
+    }
+}
+
+pub struct SensorFilter {
+    temperature: ChannelFilter,
+    humidity: ChannelFilter,
+    pressure: ChannelFilter,
+    gas_resistance: ChannelFilter,
+}
+
+impl SensorFilter {
+    pub fn new() -> Self {
+        SensorFilter {
+            temperature: ChannelFilter::new(config::TEMPERATURE_FILTER),
+            humidity: ChannelFilter::new(config::HUMIDITY_FILTER),
+            pressure: ChannelFilter::new(config::PRESSURE_FILTER),
+            gas_resistance: ChannelFilter::new(config::GAS_RESISTANCE_FILTER),
+        }
+    }
+
+    pub fn update(&mut self, unfiltered: &Measurement) -> Readings {
+        let filtered = Measurement {
+            temperature: self.temperature.update(unfiltered.temperature),
+            humidity: self.humidity.update(unfiltered.humidity),
+            pressure: self.pressure.update(unfiltered.pressure),
+            gas_resistance: unfiltered
+                .gas_resistance
+                .map(|r| self.gas_resistance.update(r as f32).max(0.0) as u32),
+        };
+        Readings {
+            unfiltered: *unfiltered,
+            filtered,
+        }
+    }
+
+    /// Call when the sensor is re-initialized, so the readings before the
+    /// failure don't reject or drag the new ones
+    pub fn reset(&mut self) {
+        self.temperature.reset();
+        self.humidity.reset();
+        self.pressure.reset();
+        self.gas_resistance.reset();
+    }
+}
+
+impl Default for SensorFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMOOTHING: FilterConfig = FilterConfig {
+        max_step: Some(1.0),
+        median_len: 3,
+        ema_alpha: 0.5,
+    };
+
+    #[test]
+    fn rejects_a_spike() {
+        let mut filter = ChannelFilter::new(SMOOTHING);
+        assert_eq!(filter.update(20.0), 20.0);
+        assert_eq!(filter.update(30.0), 20.0);
+    }
+
+    #[test]
+    fn median_of_the_window() {
+        let mut filter = ChannelFilter::new(FilterConfig {
+            median_len: 3,
+            ..FilterConfig::NONE
+        });
+        assert_eq!(filter.update(20.0), 20.0);
+        // Upper of the two while the window fills
+        assert_eq!(filter.update(22.0), 22.0);
+        assert_eq!(filter.update(21.0), 21.0);
+        assert_eq!(filter.update(25.0), 22.0);
+        assert_eq!(filter.update(19.0), 21.0);
+        assert_eq!(filter.update(19.5), 19.5);
+    }
+
+    #[test]
+    fn ema_converges_on_a_step() {
+        let mut filter = ChannelFilter::new(FilterConfig {
+            ema_alpha: 0.5,
+            ..FilterConfig::NONE
+        });
+        assert_eq!(filter.update(20.0), 20.0);
+        assert_eq!(filter.update(24.0), 22.0);
+        assert_eq!(filter.update(24.0), 23.0);
+        assert_eq!(filter.update(24.0), 23.5);
+        let mut out = 0.0;
+        for _ in 0..20 {
+            out = filter.update(24.0);
+        }
+        assert!((24.0 - out).abs() < 0.001, "{}", out);
+    }
+
+    #[test]
+    fn step_is_accepted_after_the_max_rejections() {
+        let mut filter = ChannelFilter::new(FilterConfig {
+            max_step: Some(1.0),
+            ..FilterConfig::NONE
+        });
+        assert_eq!(filter.update(20.0), 20.0);
+        for _ in 0..MAX_CONSECUTIVE_REJECTIONS {
+            assert_eq!(filter.update(25.0), 20.0);
+        }
+        assert_eq!(filter.update(25.0), 25.0);
+        // The new level is what later samples are checked against
+        assert_eq!(filter.update(25.5), 25.5);
+        assert_eq!(filter.update(20.0), 25.5);
+    }
+
+    #[test]
+    fn rejection_count_restarts_on_an_accepted_sample() {
+        let mut filter = ChannelFilter::new(FilterConfig {
+            max_step: Some(1.0),
+            ..FilterConfig::NONE
+        });
+        filter.update(20.0);
+        assert_eq!(filter.update(30.0), 20.0);
+        assert_eq!(filter.update(30.0), 20.0);
+        assert_eq!(filter.update(20.5), 20.5);
+        for _ in 0..MAX_CONSECUTIVE_REJECTIONS {
+            assert_eq!(filter.update(30.0), 20.5);
+        }
+    }
+
+    #[test]
+    fn reset_passes_the_next_sample_through() {
+        let mut filter = ChannelFilter::new(SMOOTHING);
+        for _ in 0..5 {
+            filter.update(20.0);
+        }
+        filter.reset();
+        assert_eq!(filter.update(30.0), 30.0);
+    }
+}
@@ -0,0 +1,274 @@
+//! Recent history of a few channels, for the graph views
+
+use crate::alarm::Channel;
+use crate::config;
+use crate::ring_buffer::RingBuffer;
+use crate::sensor::Measurement;
+use crate::system_clock::SystemClock;
+use crate::warm_up::WarmUp;
+use embedded_time::{duration::Seconds, Instant};
+use heapless::Vec;
+
+pub const MAX_GRAPH_CHANNELS: usize = 4;
+
+/// Samples per channel, one per pixel column of the graph
+pub const HISTORY_LEN: usize = 96;
+
+/// Each sample is the mean of the readings over this interval, so the
+/// history spans config::GRAPH_HOURS
+const SAMPLE_INTERVAL: Seconds = Seconds(config::GRAPH_HOURS * 60 * 60 / HISTORY_LEN as u32);
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum GraphStyle {
+    Line,
+    Bar,
+}
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub struct GraphConfig {
+    pub channel: Channel,
+    pub style: GraphStyle,
+    /// Smallest range of the auto-scaled axis, in the channel's units, so
+    /// sensor noise isn't magnified into a graph of a steady value
+    pub min_span: f32,
+}
+
+pub struct ChannelHistory {
+    config: GraphConfig,
+    /// NaN for intervals without readings
+    samples: RingBuffer<f32, HISTORY_LEN>,
+    sum: f32,
+    count: u32,
+    next_sample: Option<Instant<SystemClock>>,
+}
+
+impl ChannelHistory {
+    fn new(config: GraphConfig) -> Self {
+        ChannelHistory {
+            config,
+            samples: RingBuffer::new(),
+            sum: 0.0,
+            count: 0,
+            next_sample: None,
+        }
+    }
+
+    pub fn config(&self) -> &GraphConfig {
+        &self.config
+    }
+
+    /// Oldest to newest, NaN for gaps
+    pub fn samples(&self) -> impl Iterator<Item = f32> + '_ {
+        self.samples.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Mean of the readings in the current interval, not sampled yet
+    pub fn current(&self) -> Option<f32> {
+        if self.count != 0 {
+            Some(self.sum / self.count as f32)
+        } else {
+            self.samples.newest().filter(|v| v.is_finite())
+        }
+    }
+
+    /// Auto-scaled (low, high) axis range covering every sample, at least
+    /// min_span wide
+    pub fn range(&self) -> Option<(f32, f32)> {
+        let (low, high) = self
+            .samples()
+            .filter(|v| v.is_finite())
+            .fold(None, |range, v| match range {
+                None => Some((v, v)),
+                Some((low, high)) => Some((f32::min(low, v), f32::max(high, v))),
+            })?;
+        let pad = (self.config.min_span - (high - low)) / 2.0;
+        if pad > 0.0 {
+            Some((low - pad, high + pad))
+        } else {
+            Some((low, high))
+        }
+    }
+
+    fn update(&mut self, now: &Instant<SystemClock>, value: Option<f32>) {
+        if let Some(mut next_sample) = self.next_sample {
+            if now.checked_duration_since(&next_sample).is_some() {
+                let mean = if self.count != 0 {
+                    self.sum / self.count as f32
+                } else {
+                    f32::NAN
+                };
+                self.samples.push(mean);
+                self.sum = 0.0;
+                self.count = 0;
+
+                // Intervals without any readings (e.g. the sensor failed)
+                // are gaps, the time axis stays linear
+                next_sample = next_sample.checked_add(SAMPLE_INTERVAL).unwrap_or(*now);
+                let mut missed = 0;
+                while now.checked_duration_since(&next_sample).is_some() && missed < HISTORY_LEN {
+                    self.samples.push(f32::NAN);
+                    next_sample = next_sample.checked_add(SAMPLE_INTERVAL).unwrap_or(*now);
+                    missed += 1;
+                }
+                if now.checked_duration_since(&next_sample).is_some() {
+                    // The whole history is a gap, start the intervals over
+                    next_sample = now.checked_add(SAMPLE_INTERVAL).unwrap_or(*now);
+                }
+                self.next_sample.replace(next_sample);
+            }
+        } else {
+            self.next_sample = now.checked_add(SAMPLE_INTERVAL);
+        }
+        if let Some(value) = value {
+            self.sum += value;
+            self.count += 1;
+        }
+    }
+}
+
+pub struct History {
+    channels: Vec<ChannelHistory, MAX_GRAPH_CHANNELS>,
+}
+
+impl History {
+    /// Channels past MAX_GRAPH_CHANNELS are ignored
+    pub fn new(graphs: &[GraphConfig]) -> Self {
+        History {
+            channels: graphs
+                .iter()
+                .take(MAX_GRAPH_CHANNELS)
+                .map(|graph| ChannelHistory::new(*graph))
+                .collect(),
+        }
+    }
+
+    /// Call with each new measurement, readings of channels still warming
+    /// up are left out
+    pub fn update(&mut self, now: &Instant<SystemClock>, data: &Measurement, warm_up: &WarmUp) {
+        for history in self.channels.iter_mut() {
+            let channel = history.config.channel;
+            let value = if warm_up.are_settled(channel.quantities(), now) {
+                channel.value(data)
+            } else {
+                None
+            };
+            history.update(now, value);
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&ChannelHistory> {
+        self.channels.get(index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERVAL: u32 = SAMPLE_INTERVAL.0;
+
+    fn at(sec: u32) -> Instant<SystemClock> {
+        Instant::new(sec)
+    }
+
+    fn history(min_span: f32) -> ChannelHistory {
+        ChannelHistory::new(GraphConfig {
+            channel: Channel::Pressure,
+            style: GraphStyle::Line,
+            min_span,
+        })
+    }
+
+    fn samples(history: &ChannelHistory) -> Vec<f32, HISTORY_LEN> {
+        history.samples().collect()
+    }
+
+    #[test]
+    fn samples_are_interval_means() {
+        let mut history = history(4.0);
+        history.update(&at(0), Some(1010.0));
+        history.update(&at(INTERVAL / 2), Some(1012.0));
+        assert!(history.is_empty());
+        assert_eq!(history.current(), Some(1011.0));
+        history.update(&at(INTERVAL), Some(1020.0));
+        assert_eq!(samples(&history), [1011.0]);
+        assert_eq!(history.current(), Some(1020.0));
+    }
+
+    #[test]
+    fn missed_intervals_are_gaps() {
+        let mut history = history(4.0);
+        history.update(&at(0), Some(1010.0));
+        history.update(&at(INTERVAL), Some(1011.0));
+        // The sensor failed for over an interval
+        history.update(&at((3 * INTERVAL) + 10), Some(1012.0));
+        let samples = samples(&history);
+        assert_eq!(samples.len(), 3);
+        assert_eq!(samples[..2], [1010.0, 1011.0]);
+        assert!(samples[2].is_nan());
+        assert_eq!(history.current(), Some(1012.0));
+
+        // Resumes on the original interval boundaries
+        history.update(&at(4 * INTERVAL), Some(1013.0));
+        assert_eq!(history.samples().last(), Some(1012.0));
+    }
+
+    #[test]
+    fn intervals_without_readings_are_gaps() {
+        let mut history = history(4.0);
+        history.update(&at(0), None);
+        history.update(&at(INTERVAL / 2), None);
+        history.update(&at(INTERVAL), Some(1010.0));
+        assert!(history.samples().all(f32::is_nan));
+        assert_eq!(history.len(), 1);
+        assert_eq!(history.range(), None);
+    }
+
+    #[test]
+    fn long_gap_is_capped_at_the_history_length() {
+        let mut history = history(4.0);
+        history.update(&at(0), Some(1010.0));
+        let resumed = 1000 * INTERVAL;
+        history.update(&at(resumed), Some(1012.0));
+        assert_eq!(history.len(), HISTORY_LEN);
+        assert!(history.samples().all(f32::is_nan));
+
+        // The next readings aren't pushed out by more gaps
+        history.update(&at(resumed + 15), Some(1014.0));
+        assert_eq!(history.current(), Some(1013.0));
+        history.update(&at(resumed + INTERVAL), Some(1016.0));
+        assert_eq!(history.samples().last(), Some(1013.0));
+        assert_eq!(history.samples().filter(|v| v.is_finite()).count(), 1);
+    }
+
+    #[test]
+    fn flat_signal_is_padded_to_the_min_span() {
+        let mut history = history(4.0);
+        for i in 0..4 {
+            history.update(&at(i * INTERVAL), Some(1013.0));
+        }
+        assert_eq!(history.range(), Some((1011.0, 1015.0)));
+
+        history.update(&at(4 * INTERVAL), Some(1014.0));
+        history.update(&at(5 * INTERVAL), Some(1014.0));
+        assert_eq!(history.range(), Some((1011.5, 1015.5)));
+    }
+
+    #[test]
+    fn wide_range_is_not_padded() {
+        let mut history = history(4.0);
+        for (i, value) in [1000.0, 1010.0, 1005.0].iter().enumerate() {
+            history.update(&at(i as u32 * INTERVAL), Some(*value));
+        }
+        history.update(&at(3 * INTERVAL), None);
+        assert_eq!(history.range(), Some((1000.0, 1010.0)));
+    }
+}
@@ -0,0 +1,133 @@
+//! I2C bus shared through proxies, so the device driver can be dropped and
+//! re-created while the bus itself is released and recovered
+
+use core::cell::RefCell;
+use embedded_hal::blocking::i2c::{Read, Write};
+
+#[derive(Debug)]
+pub enum Error<E> {
+    /// Error from the underlying bus
+    Bus(E),
+    /// The bus is being recovered
+    Unavailable,
+}
+
+pub struct I2cBus<I2C, F> {
+    i2c: RefCell<Option<I2C>>,
+    recover: RefCell<F>,
+}
+
+impl<I2C, F> I2cBus<I2C, F>
+where
+    F: FnMut(I2C) -> I2C,
+{
+    /// `recover` is handed the bus when it needs to be released (typically
+    /// by clocking SCL until a slave holding SDA low lets go) and returns
+    /// the re-initialized bus
+    pub fn new(i2c: I2C, recover: F) -> Self {
+        I2cBus {
+            i2c: RefCell::new(Some(i2c)),
+            recover: RefCell::new(recover),
+        }
+    }
+
+    pub fn proxy(&self) -> I2cProxy<'_, I2C> {
+        I2cProxy { i2c: &self.i2c }
+    }
+
+    pub fn recover(&self) {
+        let mut i2c = self.i2c.borrow_mut();
+        if let Some(bus) = i2c.take() {
+            let mut recover = self.recover.borrow_mut();
+            i2c.replace((&mut *recover)(bus));
+        }
+    }
+}
+
+pub struct I2cProxy<'a, I2C> {
+    i2c: &'a RefCell<Option<I2C>>,
+}
+
+impl<'a, I2C> Read for I2cProxy<'a, I2C>
+where
+    I2C: Read,
+{
+    type Error = Error<<I2C as Read>::Error>;
+
+    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+        match self.i2c.borrow_mut().as_mut() {
+            Some(i2c) => i2c.read(address, buffer).map_err(Error::Bus),
+            None => Err(Error::Unavailable),
+        }
+    }
+}
+
+impl<'a, I2C> Write for I2cProxy<'a, I2C>
+where
+    I2C: Write,
+{
+    type Error = Error<<I2C as Write>::Error>;
+
+    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+        match self.i2c.borrow_mut().as_mut() {
+            Some(i2c) => i2c.write(address, bytes).map_err(Error::Bus),
+            None => Err(Error::Unavailable),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::cell::Cell;
+
+    /// Fails every transfer while `stuck`, as a slave holding SDA low would
+    struct MockI2c {
+        stuck: bool,
+    }
+
+    impl Read for MockI2c {
+        type Error = ();
+
+        fn read(&mut self, _address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
+            if self.stuck {
+                return Err(());
+            }
+            buffer.fill(0x61);
+            Ok(())
+        }
+    }
+
+    impl Write for MockI2c {
+        type Error = ();
+
+        fn write(&mut self, _address: u8, _bytes: &[u8]) -> Result<(), Self::Error> {
+            if self.stuck {
+                Err(())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn recovery_releases_a_stuck_bus() {
+        let recoveries = Cell::new(0);
+        let bus = I2cBus::new(MockI2c { stuck: true }, |mut i2c: MockI2c| {
+            recoveries.set(recoveries.get() + 1);
+            i2c.stuck = false;
+            i2c
+        });
+        let mut proxy = bus.proxy();
+        let mut buffer = [0; 2];
+        assert!(matches!(proxy.read(0x77, &mut buffer), Err(Error::Bus(()))));
+        assert!(matches!(proxy.write(0x77, &[0xd0]), Err(Error::Bus(()))));
+
+        bus.recover();
+        assert_eq!(recoveries.get(), 1);
+        // Proxies handed out before the recovery keep working
+        assert!(proxy.read(0x77, &mut buffer).is_ok());
+        assert_eq!(buffer, [0x61; 2]);
+        assert!(bus.proxy().write(0x77, &[0xd0]).is_ok());
+    }
+}
@@ -0,0 +1,38 @@
+//! Hardware independent parts of the logger, also built for the host so the
+//! tests run with `cargo test-host`
+
+#![deny(warnings, clippy::all)]
+#![no_std]
+
+pub mod actuator;
+pub mod alarm;
+pub mod alarm_log;
+pub mod atomic_button_state;
+pub mod barometer;
+pub mod buzzer;
+pub mod calibration;
+pub mod compensation;
+pub mod config;
+pub mod console;
+pub mod daily_extremes;
+pub mod display;
+pub mod file_system;
+pub mod filter;
+pub mod history;
+pub mod i2c_bus;
+pub mod notification;
+pub mod psychro;
+pub mod rate;
+pub mod record;
+pub mod ring_buffer;
+pub mod rtc;
+pub mod schedule;
+pub mod sensor;
+pub mod settings;
+pub mod sun;
+pub mod system_clock;
+pub mod system_status;
+pub mod units;
+pub mod util;
+pub mod view_mode_switcher;
+pub mod warm_up;
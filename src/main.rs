@@ -5,53 +5,64 @@
 use panic_abort as _;
 use stm32f4xx_hal as hal;
 
-use crate::alarm::Alarm;
-use crate::atomic_button_state::AtomicButtonState;
-use crate::display::{Display, View};
 use crate::error::Error;
-use crate::file_system::FileSystem;
 use crate::hal::{
+    adc::{
+        config::{AdcConfig, SampleTime},
+        Adc, Temperature,
+    },
     delay::Delay,
     gpio::gpioa::PA0,
     gpio::{Edge, Input, PullUp},
     i2c::I2c,
     interrupt,
     prelude::*,
-    serial::{config::Config as SerialConfig, Serial},
+    serial::{config::Config as SerialConfig, Event as SerialEvent, Rx, Serial},
+    signature::{VtempCal110, VtempCal30},
     spi::Spi,
     stm32,
     timer::Timer,
     watchdog::IndependentWatchdog,
 };
-use crate::record::Record;
-use crate::rtc::Rtc;
-use crate::sensor::{DelayWrapper, Sensor};
-use crate::system_clock::{SystemClock, SystemClockRef};
-use crate::system_status::SystemStatus;
-use crate::view_mode_switcher::{ViewMode, ViewModeSwitcher};
 use core::cell::RefCell;
 use core::fmt::Write;
 use core::ops::DerefMut;
 use cortex_m::interrupt::{free, Mutex};
 use cortex_m_rt::{entry, exception, ExceptionFrame};
+use environment_logger::actuator::{relay_off_state, Actuator, Relay};
+use environment_logger::alarm::{Alarm, EventKind, MAX_RATE_CHANNELS};
+use environment_logger::alarm_log::{self, AlarmLog};
+use environment_logger::atomic_button_state::{AtomicButtonState, ButtonPress};
+use environment_logger::barometer::{self, Barometer};
+use environment_logger::buzzer::Pattern;
+use environment_logger::calibration::{ChannelCalibration, TwoPointCalibration};
+use environment_logger::compensation::{self, HeatSources, SelfHeating};
+use environment_logger::config;
+use environment_logger::console::{Command, Console};
+use environment_logger::daily_extremes::{self, DailyExtremes};
+use environment_logger::display::{Display, View, ViewContext};
+use environment_logger::file_system::FileSystem;
+use environment_logger::filter::SensorFilter;
+use environment_logger::history::History;
+use environment_logger::i2c_bus::I2cBus;
+use environment_logger::notification::{Cause, Notification, Notifications};
+use environment_logger::record::Record;
+use environment_logger::rtc::Rtc;
+use environment_logger::sensor::{DelayWrapper, Sensor, SensorHealth};
+use environment_logger::settings::{Settings, SETTINGS_STRING_CAP};
+use environment_logger::system_clock::{SystemClock, SystemClockRef, TICK_HZ};
+use environment_logger::system_status::SystemStatus;
+use environment_logger::view_mode_switcher::ViewModeSwitcher;
+use heapless::Vec;
 use ssd1306::I2CDisplayInterface;
 
-mod alarm;
-mod atomic_button_state;
-mod display;
 mod error;
-mod file_system;
-mod record;
-mod rtc;
-mod sensor;
-mod system_clock;
-mod system_status;
-mod util;
-mod view_mode_switcher;
 
 static SYS_CLOCK: SystemClock = SystemClock::new();
 static BUTTON: AtomicButtonState = AtomicButtonState::new();
 static BUTTON_GPIO: Mutex<RefCell<Option<PA0<Input<PullUp>>>>> = Mutex::new(RefCell::new(None));
+static SERIAL_RX: Mutex<RefCell<Option<Rx<stm32::USART2>>>> = Mutex::new(RefCell::new(None));
+static SERIAL_RX_BYTES: Mutex<RefCell<Vec<u8, 32>>> = Mutex::new(RefCell::new(Vec::new()));
 
 #[entry]
 fn main() -> ! {
@@ -89,7 +100,7 @@ fn do_main() -> Result<(), Error> {
     let mut btn = gpioa.pa0.into_pull_up_input();
     btn.make_interrupt_source(&mut syscfg);
     btn.enable_interrupt(&mut dp.EXTI);
-    btn.trigger_on_edge(&mut dp.EXTI, Edge::Falling);
+    btn.trigger_on_edge(&mut dp.EXTI, Edge::RisingFalling);
 
     // General purpose delay timer from TIM5
     let delay = Delay::tim5(dp.TIM5, &clocks);
@@ -97,26 +108,59 @@ fn do_main() -> Result<(), Error> {
 
     // Alarm buzzer, PA10, PWM on T1_CH3
     let pwm_channels = gpioa.pa10.into_alternate();
-    let buzzer = Timer::new(dp.TIM1, &clocks).pwm(pwm_channels, 5_u32.khz());
+    let buzzer = Timer::new(dp.TIM1, &clocks).pwm(pwm_channels, config::BUZZER_FREQUENCY_HZ.hz());
     let mut alarm = Alarm::new(buzzer);
 
-    // Short beep on power up
-    led.set_low();
-    alarm.set_on_off(true);
-    delay.delay_ms(200_u8);
-    alarm.set_on_off(false);
-    led.set_high();
+    // Short beep on power up, plays once the SysTick is running
+    alarm.play(Pattern::Boot);
     alarm.set_monitoring(true);
 
-    // USART2, debug serial
+    // Relay outputs
+    // PB8, relay 1
+    // PB9, relay 2
+    // PB12, relay 3
+    let active_low = |i: usize| config::ACTUATORS[i].map_or(false, |a| a.active_low);
+    let mut relay1 = Relay::new(
+        gpiob
+            .pb8
+            .into_push_pull_output_in_state(relay_off_state(active_low(0))),
+        active_low(0),
+    );
+    let mut relay2 = Relay::new(
+        gpiob
+            .pb9
+            .into_push_pull_output_in_state(relay_off_state(active_low(1))),
+        active_low(1),
+    );
+    let mut relay3 = Relay::new(
+        gpiob
+            .pb12
+            .into_push_pull_output_in_state(relay_off_state(active_low(2))),
+        active_low(2),
+    );
+    let mut actuators = [
+        config::ACTUATORS[0].map(Actuator::new),
+        config::ACTUATORS[1].map(Actuator::new),
+        config::ACTUATORS[2].map(Actuator::new),
+    ];
+
+    // ADC1, MCU internal temperature sensor
+    let mut adc = Adc::adc1(dp.ADC1, true, AdcConfig::default());
+    adc.enable_temperature_and_vref();
+
+    // USART2, debug serial and console
     // PA2, TX2
+    // PA3, RX2
     let tx = gpioa.pa2.into_alternate();
-    let mut stdout = Serial::tx(
+    let rx = gpioa.pa3.into_alternate();
+    let mut serial = Serial::new(
         dp.USART2,
-        tx,
+        (tx, rx),
         SerialConfig::default().baudrate(115200.bps()),
         clocks,
     )?;
+    serial.listen(SerialEvent::Rxne);
+    let (mut stdout, stdin) = serial.split();
     writeln!(stdout, "---------------------------")?;
     writeln!(stdout, "Starting")?;
 
@@ -143,7 +187,32 @@ fn do_main() -> Result<(), Error> {
     let bme_scl = gpiob.pb10.into_alternate().set_open_drain();
     let bme_sda = gpiob.pb3.into_alternate().set_open_drain();
     let bme_i2c = I2c::new(dp.I2C2, (bme_scl, bme_sda), 100.khz(), clocks);
-    let mut sensor = Sensor::new(bme_i2c, &SYS_CLOCK.now(), &mut delay)?;
+    let bme_bus = I2cBus::new(bme_i2c, move |i2c| {
+        let (i2c2, (scl, sda)) = i2c.release();
+        let mut scl = scl.into_open_drain_output();
+        let mut sda = sda.into_open_drain_output();
+        sda.set_high();
+        // Clock SCL until a slave stuck mid-byte releases SDA, 5 us half period
+        let half_period = clocks.sysclk().0 / 200_000;
+        for _ in 0..9 {
+            scl.set_low();
+            cortex_m::asm::delay(half_period);
+            scl.set_high();
+            cortex_m::asm::delay(half_period);
+        }
+        // STOP (SDA rising while SCL is high) ends the slave's transaction
+        scl.set_low();
+        sda.set_low();
+        cortex_m::asm::delay(half_period);
+        scl.set_high();
+        cortex_m::asm::delay(half_period);
+        sda.set_high();
+        cortex_m::asm::delay(half_period);
+        let scl = scl.into_alternate().set_open_drain();
+        let sda = sda.into_alternate().set_open_drain();
+        I2c::new(i2c2, (scl, sda), 100.khz(), clocks)
+    });
+    let mut sensor = Sensor::new(&bme_bus, &SYS_CLOCK.now(), &mut delay);
 
     // SPI1, SD card
     // PA15, NSS1
@@ -170,49 +239,170 @@ fn do_main() -> Result<(), Error> {
     writeln!(stdout, "Now: {}", sys_clock_ref.base_datetime)?;
     let mut fs = FileSystem::new(sd_spi, sd_cs, sys_clock_ref)?;
 
-    SYS_CLOCK.enable_systick_interrupt(cp.SYST, &clocks);
+    SYS_CLOCK.enable_systick_interrupt(cp.SYST, clocks.hclk().0);
     watchdog.feed();
 
     let mut status = SystemStatus::default();
-    let mut view_mode_switcher = ViewModeSwitcher::new(SYS_CLOCK.now());
+    let mut view_mode_switcher =
+        ViewModeSwitcher::new(config::VIEW_ROTATION, config::PINNED_VIEW, SYS_CLOCK.now());
 
     free(|cs| {
         BUTTON_GPIO.borrow(cs).replace(Some(btn));
+        SERIAL_RX.borrow(cs).replace(Some(stdin));
     });
 
     // Enable interrupts
     stm32::NVIC::unpend(stm32::Interrupt::EXTI0);
+    stm32::NVIC::unpend(stm32::Interrupt::USART2);
     unsafe {
         stm32::NVIC::unmask(stm32::Interrupt::EXTI0);
+        stm32::NVIC::unmask(stm32::Interrupt::USART2);
     };
 
-    let boot_time_sec = SYS_CLOCK.now();
+    let mut settings = Settings::new();
+    let mut console = Console::new();
+    let mut two_point_calibration = TwoPointCalibration::default();
+    let mut raw_measurement = None;
     let mut sensor_data = None;
+    let mut sensor_filter = SensorFilter::new();
+    let mut barometer = Barometer::new();
+    let mut history = History::new(config::GRAPHS);
+    let mut daily_extremes = DailyExtremes::new();
+    // Finished days not yet written, kept until the card is initialized
+    let mut pending_daily: Vec<DailyExtremes, 7> = Vec::new();
+    let mut alarm_log = AlarmLog::new();
+    let mut notifications = Notifications::new();
+    let mut last_notification: Option<Notification> = None;
+    // Forces the next record, so every actuation is recorded
+    let mut actuation_pending = false;
+    let mut last_sec = None;
 
     loop {
         cortex_m::asm::wfi();
+        let ticks = SYS_CLOCK.ticks();
+        let now = SYS_CLOCK.now();
+
+        let mut button_press = BUTTON.poll(ticks);
+        if button_press.is_some() && display.wake(&now) {
+            // The press only woke the dark screen
+            button_press = None;
+        }
+        match button_press {
+            Some(ButtonPress::Short) => {
+                // Acknowledge/snooze a sounding alarm
+                if alarm.acknowledge(&now) {
+                    alarm.play(Pattern::Chirp);
+                }
+                // Dismiss the notification, the next one or the status view
+                // follows
+                if notifications.dismiss().is_none() {
+                    view_mode_switcher.set_mode(View::SYSTEM_STATUS, &now);
+                }
+            }
+            Some(ButtonPress::Long) => {
+                alarm.set_monitoring(!alarm.monitoring());
+                alarm.play(Pattern::Chirp);
+                view_mode_switcher.set_mode(View::SYSTEM_STATUS, &now);
+            }
+            None => (),
+        }
+
+        alarm.update_buzzer(ticks);
+        if alarm.visual_only() {
+            // Flash the LED in place of the muted buzzer, 2 Hz
+            if (ticks / (TICK_HZ / 4)) % 2 == 0 {
+                led.set_low();
+            } else {
+                led.set_high();
+            }
+        }
+
+        // Everything else runs once a second
+        if last_sec == Some(now) {
+            continue;
+        }
+        last_sec.replace(now);
+
         watchdog.feed();
-        led.toggle();
+        if !alarm.visual_only() {
+            led.toggle();
+        }
+
+        // Compared at the end of the second for failures to notify
+        let last_status = status;
 
-        let now = SYS_CLOCK.now();
         if sd_det.is_high() {
             // SD connected
             if !status.storage_error && !fs.is_init() {
                 match fs.init(&now) {
-                    Ok(()) => writeln!(stdout, "Storage init")?,
+                    Ok(()) => {
+                        writeln!(stdout, "Storage init")?;
+                        let mut buf = [0_u8; SETTINGS_STRING_CAP];
+                        match fs.read_settings(&mut buf) {
+                            Ok(Some(len)) => {
+                                if let Ok(text) = core::str::from_utf8(&buf[..len]) {
+                                    settings.parse(text);
+                                    writeln!(stdout, "Settings loaded")?;
+                                }
+                            }
+                            Ok(None) => (),
+                            Err(e) => {
+                                status.storage_error = true;
+                                writeln!(stdout, "Storage read err: {:?}", e)?;
+                            }
+                        }
+                        writeln!(stdout, "Calibration: {}", settings.calibration)?;
+                        writeln!(stdout, "Units: {}", settings.units)?;
+                        display.set_units(settings.units);
+                        display.set_time_format(settings.time_format);
+                        // Replacing the rules clears the active alarms and
+                        // the snooze, not wanted on every card re-insert
+                        if alarm.rules().rules() != settings.alarm_rules.as_slice() {
+                            alarm.set_rules(&settings.alarm_rules);
+                        }
+                        for rule in settings.alarm_rules.iter() {
+                            let rule = settings.units.rule_from_internal(rule);
+                            writeln!(stdout, "Alarm rule: {}", rule)?;
+                        }
+                        for rule in alarm.rules().untracked_rules() {
+                            let rule = settings.units.rule_from_internal(rule);
+                            writeln!(
+                                stdout,
+                                "Alarm rule ignored, over {} rate channels: {}",
+                                MAX_RATE_CHANNELS, rule
+                            )?;
+                        }
+                        let header = Record::csv_header(&settings.calibration, &settings.units)?;
+                        if let Err(e) = fs.write_header(header.as_bytes()) {
+                            status.storage_error = true;
+                            writeln!(stdout, "Storage write err: {:?}", e)?;
+                        }
+                        if let Err(e) =
+                            fs.write_alarm_log(alarm_log::csv_header(&settings.units)?.as_bytes())
+                        {
+                            status.storage_error = true;
+                            writeln!(stdout, "Storage write err: {:?}", e)?;
+                        }
+                        if let Err(e) =
+                            fs.write_daily(daily_extremes::csv_header(&settings.units)?.as_bytes())
+                        {
+                            status.storage_error = true;
+                            writeln!(stdout, "Storage write err: {:?}", e)?;
+                        }
+                    }
                     Err(e) => {
                         status.storage_error = true;
                         writeln!(stdout, "Storage init err: {:?}", e)?;
                     }
                 }
-                view_mode_switcher.set_mode(ViewMode::SystemStatus, &now);
+                view_mode_switcher.set_mode(View::SYSTEM_STATUS, &now);
             }
         } else {
             // SD not connected
             if fs.is_init() {
                 writeln!(stdout, "Storage disconnected")?;
                 fs.deinit();
-                view_mode_switcher.set_mode(ViewMode::SystemStatus, &now);
+                view_mode_switcher.set_mode(View::SYSTEM_STATUS, &now);
             }
             status.clear_storage_status();
         }
@@ -220,73 +410,303 @@ fn do_main() -> Result<(), Error> {
 
         let now = SYS_CLOCK.now();
 
-        if BUTTON.get_and_clear() {
-            alarm.set_monitoring(!alarm.monitoring());
-            view_mode_switcher.set_mode(ViewMode::SystemStatus, &now);
+        let rx_bytes =
+            free(|cs| core::mem::take(SERIAL_RX_BYTES.borrow(cs).borrow_mut().deref_mut()));
+        for byte in rx_bytes.iter() {
+            let cmd = match console.push(*byte) {
+                Some(cmd) => cmd,
+                None => continue,
+            };
+            let updated = match (cmd, &raw_measurement) {
+                (Command::ShowCalibration, _) => false,
+                (Command::CalibrationLow { channel, reference }, Some(raw)) => {
+                    two_point_calibration.set_low(channel, channel.value(raw), reference);
+                    writeln!(stdout, "Captured {} low point", channel.name())?;
+                    false
+                }
+                (Command::CalibrationHigh { channel, reference }, Some(raw)) => {
+                    match two_point_calibration.set_high(channel, channel.value(raw), reference) {
+                        Some(cal) => {
+                            *settings.calibration.channel_mut(channel) = cal;
+                            true
+                        }
+                        None => {
+                            writeln!(stdout, "Capture a distinct low point first")?;
+                            false
+                        }
+                    }
+                }
+                (Command::CalibrationOffset { channel, reference }, Some(raw)) => {
+                    settings
+                        .calibration
+                        .channel_mut(channel)
+                        .set_offset(channel.value(raw), reference);
+                    true
+                }
+                (Command::CalibrationReset { channel }, _) => {
+                    *settings.calibration.channel_mut(channel) = ChannelCalibration::IDENTITY;
+                    true
+                }
+                (Command::Unknown, _) => {
+                    writeln!(stdout, "Unknown command")?;
+                    false
+                }
+                (_, None) => {
+                    writeln!(stdout, "No sensor reading yet")?;
+                    false
+                }
+            };
+            if updated {
+                if let Err(e) = fs.write_settings(settings.serialize()?.as_bytes()) {
+                    status.storage_error = true;
+                    writeln!(stdout, "Storage write err: {:?}", e)?;
+                }
+            }
+            writeln!(stdout, "Calibration: {}", settings.calibration)?;
         }
 
         let dt = rtc.get_datetime()?;
-        if let Some(new_sensor_data) = sensor.poll(&now, &mut delay)? {
-            // Check the alarm if monitoring and warm-up period has elapsed
-            if !status.alarm_warmed_up {
-                if let Some(time_since_boot) = now.checked_duration_since(&boot_time_sec) {
-                    if time_since_boot >= Alarm::<usize>::WARM_UP_DELAY.into() {
-                        status.alarm_warmed_up = true;
-                        writeln!(stdout, "Alarm warmed up {}", dt)?;
-                    }
+        alarm.set_quiet_hours(config::QUIET_HOURS.contains(&dt));
+        if let Some(finished) = daily_extremes.roll_over(&dt.date()) {
+            write!(
+                stdout,
+                "Daily summary {}",
+                finished.to_csv_line(&settings.units)?
+            )?;
+            if pending_daily.push(finished).is_err() {
+                writeln!(stdout, "Daily summary dropped, storage unavailable")?;
+            }
+        }
+        if fs.is_init() && !status.storage_error && !pending_daily.is_empty() {
+            let mut written = 0;
+            for finished in pending_daily.iter() {
+                if let Err(e) = fs.write_daily(finished.to_csv_line(&settings.units)?.as_bytes()) {
+                    status.storage_error = true;
+                    writeln!(stdout, "Storage write err: {:?}", e)?;
+                    break;
                 }
+                written += 1;
             }
-
-            if status.alarm_warmed_up {
-                alarm.check_temperature(&new_sensor_data);
+            // The unwritten ones are retried after the next init
+            pending_daily = Vec::from_slice(&pending_daily[written..]).unwrap_or_default();
+        }
+        for actuator in actuators.iter_mut().flatten() {
+            if let Some(actuation) = actuator.check_max_on_time(&now) {
+                actuation_pending = true;
+                writeln!(stdout, "{} {} {}", actuator.config().name, actuation, dt)?;
             }
-
-            if fs.is_init() {
-                let record = Record::new(&dt, &new_sensor_data)?;
-                let csv_line = record.to_csv_line()?;
-
-                match fs.write(&now, csv_line.as_bytes()) {
-                    Ok(did_write) => {
-                        if did_write {
-                            status.inc_records();
-                        }
-                    }
-                    Err(e) => {
-                        status.storage_error = true;
-                        writeln!(stdout, "Storage write err: {:?}", e)?;
-                        if let embedded_sdmmc::Error::NotEnoughSpace = e {
-                            status.storage_full = true;
-                        }
+        }
+        let mut record = None;
+        if let Some(measurement) = sensor.poll(&now, &mut delay) {
+            raw_measurement.replace(measurement);
+            let measurement = settings.calibration.apply(&measurement);
+            let mut heat_sources = HeatSources {
+                mcu_temperature: compensation::mcu_temperature(
+                    adc.convert(&Temperature, SampleTime::Cycles_480),
+                    VtempCal30::get().read(),
+                    VtempCal110::get().read(),
+                ),
+                rtc_temperature: f32::NAN,
+                brightness: display.brightness_level(),
+                uptime_sec: SYS_CLOCK.get_raw(),
+            };
+            let self_heating = match rtc.get_temperature() {
+                Ok(rtc_temperature) => {
+                    heat_sources.rtc_temperature = rtc_temperature;
+                    config::SELF_HEATING.compensate(&heat_sources, &measurement)
+                }
+                Err(e) => {
+                    // Uncompensated for this reading rather than stop logging
+                    writeln!(stdout, "RTC temperature err: {:?}", e)?;
+                    SelfHeating {
+                        sources: heat_sources,
+                        correction: 0.0,
+                        measurement,
                     }
                 }
+            };
+            let new_sensor_data = sensor_filter.update(&self_heating.measurement);
+
+            // Channels still warming up are skipped
+            alarm.check(&now, &new_sensor_data.filtered, sensor.warm_up());
+
+            for actuator in actuators.iter_mut().flatten() {
+                let actuation = actuator.update(
+                    &now,
+                    &new_sensor_data.filtered,
+                    alarm.rules(),
+                    sensor.warm_up(),
+                );
+                if let Some(actuation) = actuation {
+                    actuation_pending = true;
+                    writeln!(stdout, "{} {} {}", actuator.config().name, actuation, dt)?;
+                }
             }
 
+            record.replace(Record::new(
+                &dt,
+                new_sensor_data.recorded(),
+                &self_heating,
+                &sensor.warm_up().settling(&now),
+                &actuators,
+                &settings.units,
+            )?);
+
+            barometer.update(
+                &now,
+                barometer::sea_level_pressure(
+                    new_sensor_data.filtered.pressure,
+                    config::STATION_ALTITUDE_M,
+                    new_sensor_data.filtered.temperature,
+                ),
+            );
+
+            history.update(&now, &new_sensor_data.filtered, sensor.warm_up());
+            daily_extremes.update(&dt, &now, &new_sensor_data.filtered, sensor.warm_up());
+
             sensor_data.replace(new_sensor_data);
         }
 
-        status.alarm = alarm.status();
-        status.uptime_sec = SYS_CLOCK.get_raw();
-
-        display.update_brightness(&dt.time())?;
+        for event in alarm.take_events().iter() {
+            if let (EventKind::Raised, Some(rule)) = (event.kind, event.rule) {
+                if event.severity >= config::NOTIFICATION_MIN_SEVERITY {
+                    notifications.push(Notification {
+                        cause: Cause::Alarm {
+                            rule,
+                            value: event.value,
+                        },
+                        datetime: dt,
+                    });
+                }
+            }
+            let logged = alarm_log.push(&dt, event);
+            let csv_line = logged.to_csv_line(&settings.units)?;
+            write!(stdout, "Alarm event {}", csv_line)?;
+            if let Err(e) = fs.write_alarm_log(csv_line.as_bytes()) {
+                status.storage_error = true;
+                writeln!(stdout, "Storage write err: {:?}", e)?;
+            }
+        }
 
-        let view_mode = view_mode_switcher.mode(&now);
-        match view_mode {
-            ViewMode::Time => {
-                display.draw_view(View::Time { data: &dt.time() })?;
+        let alarm_status = alarm.status();
+        if alarm_status != status.alarm {
+            writeln!(stdout, "Alarm {} {}", alarm_status, dt)?;
+            for rule in alarm.rules().active_rules() {
+                writeln!(stdout, "  {}", settings.units.rule_from_internal(rule))?;
+            }
+        }
+        status.alarm = alarm_status;
+        status.sensor_health = sensor.health();
+        status.sensor_errors = sensor.error_count();
+        let warm_up_remaining_sec = sensor.warm_up().remaining_sec(&now);
+        if warm_up_remaining_sec == 0 && status.warm_up_remaining_sec != 0 {
+            writeln!(stdout, "Sensor settled {}", dt)?;
+        }
+        status.warm_up_remaining_sec = warm_up_remaining_sec;
+        if status.sensor_health != SensorHealth::Ok {
+            // Don't show or calibrate against stale readings while the reads
+            // are failing
+            sensor_data = None;
+            raw_measurement = None;
+        }
+        if status.sensor_health == SensorHealth::Failed {
+            // The re-initialized sensor starts the filters over
+            sensor_filter.reset();
+
+            // Nor sound on the last readings, logged with the next events
+            alarm.sensor_failed();
+
+            // Don't control on stale readings either
+            for actuator in actuators.iter_mut().flatten() {
+                if let Some(actuation) = actuator.shutdown(&now) {
+                    actuation_pending = true;
+                    writeln!(stdout, "{} {} {}", actuator.config().name, actuation, dt)?;
+                }
             }
-            ViewMode::Date => {
-                display.draw_view(View::Date { data: &dt.date() })?;
+            if actuation_pending {
+                // No readings are coming to record the actuations with
+                record.replace(Record::sensor_failure(&dt, &actuators, &settings.units)?);
             }
-            ViewMode::SensorReadings => {
-                if let Some(sensor_data) = &sensor_data {
-                    display.draw_view(View::SensorReadings { data: sensor_data })?;
-                } else {
-                    view_mode_switcher.skip(&now);
+        }
+        if let (Some(record), true) = (&record, fs.is_init()) {
+            let csv_line = record.to_csv_line()?;
+
+            // Every actuation is recorded
+            match fs.write(&now, csv_line.as_bytes(), actuation_pending) {
+                Ok(did_write) => {
+                    if did_write {
+                        status.inc_records();
+                    }
+                }
+                Err(e) => {
+                    status.storage_error = true;
+                    writeln!(stdout, "Storage write err: {:?}", e)?;
+                    if let embedded_sdmmc::Error::NotEnoughSpace = e {
+                        status.storage_full = true;
+                    }
                 }
             }
-            ViewMode::SystemStatus => {
-                display.draw_view(View::SystemStatus { data: &status })?;
+        }
+        if record.is_some() {
+            actuation_pending = false;
+        }
+        let failures = [
+            (
+                status.sensor_health == SensorHealth::Failed
+                    && last_status.sensor_health != SensorHealth::Failed,
+                Cause::SensorFailure,
+            ),
+            (
+                status.storage_error && !last_status.storage_error,
+                Cause::StorageError,
+            ),
+            (
+                status.storage_full && !last_status.storage_full,
+                Cause::StorageFull,
+            ),
+        ];
+        for (failed, cause) in failures.iter() {
+            if *failed {
+                notifications.push(Notification {
+                    cause: *cause,
+                    datetime: dt,
+                });
+            }
+        }
+        // Preempted rather than set_mode, which would time out after the
+        // view's duration. The alert stays until dismissed and a status view
+        // requested meanwhile waits behind it.
+        if notifications.current().is_some() {
+            view_mode_switcher.preempt(View::NOTIFICATION);
+            if notifications.current() != last_notification.as_ref() {
+                // A new notification turns the screen on
+                display.wake(&now);
             }
+        } else {
+            view_mode_switcher.release(&now);
+        }
+        last_notification = notifications.current().copied();
+
+        relay1.set(actuators[0].as_ref().map_or(false, Actuator::is_on));
+        relay2.set(actuators[1].as_ref().map_or(false, Actuator::is_on));
+        relay3.set(actuators[2].as_ref().map_or(false, Actuator::is_on));
+        status.uptime_sec = SYS_CLOCK.get_raw();
+
+        display.update_brightness(&now, &dt)?;
+
+        let view = view_mode_switcher.mode(&now);
+        let context = ViewContext {
+            datetime: &dt,
+            sensor: sensor_data.as_ref().map(|d| &d.filtered),
+            barometer: &barometer,
+            daily_extremes: &daily_extremes,
+            history: &history,
+            alarm_log: &alarm_log,
+            status: &status,
+            notification: notifications.current(),
+        };
+        if !display.draw_view(&view, &context)? {
+            view_mode_switcher.skip(&now);
         }
     }
 }
@@ -302,7 +722,25 @@ fn EXTI0() {
         let mut btn_ref = BUTTON_GPIO.borrow(cs).borrow_mut();
         if let Some(ref mut btn) = btn_ref.deref_mut() {
             btn.clear_interrupt_pending_bit();
-            BUTTON.set();
+            // Pulled up, low while pressed
+            if btn.is_low() {
+                BUTTON.press(SYS_CLOCK.ticks());
+            } else {
+                BUTTON.release(SYS_CLOCK.ticks());
+            }
+        }
+    });
+}
+
+#[interrupt]
+fn USART2() {
+    free(|cs| {
+        let mut rx_ref = SERIAL_RX.borrow(cs).borrow_mut();
+        if let Some(ref mut rx) = rx_ref.deref_mut() {
+            while let Ok(byte) = rx.read() {
+                // Dropped if the main loop falls behind
+                let _ = SERIAL_RX_BYTES.borrow(cs).borrow_mut().push(byte);
+            }
         }
     });
 }
@@ -0,0 +1,259 @@
+//! Alerts that preempt the display rotation until dismissed by the button,
+//! highest priority first
+
+use crate::alarm::{AlarmRule, Severity};
+use ds323x::NaiveDateTime;
+use heapless::Vec;
+
+/// Pending notifications, the lowest priority is dropped when full
+const MAX_NOTIFICATIONS: usize = 8;
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub enum Cause {
+    /// An alarm rule was raised with the value, in the internal units
+    Alarm {
+        rule: AlarmRule,
+        value: Option<f32>,
+    },
+    SensorFailure,
+    StorageError,
+    StorageFull,
+}
+
+impl Cause {
+    pub fn priority(&self) -> Severity {
+        match self {
+            Cause::Alarm { rule, .. } => rule.severity,
+            // Alarms can't be checked without readings
+            Cause::SensorFailure => Severity::Critical,
+            Cause::StorageError | Cause::StorageFull => Severity::Warning,
+        }
+    }
+
+    /// The same rule or the same kind of failure
+    fn same_source(&self, other: &Cause) -> bool {
+        match (self, other) {
+            (Cause::Alarm { rule: a, .. }, Cause::Alarm { rule: b, .. }) => a == b,
+            _ => core::mem::discriminant(self) == core::mem::discriminant(other),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub struct Notification {
+    pub cause: Cause,
+    pub datetime: NaiveDateTime,
+}
+
+pub struct Notifications {
+    pending: Vec<Notification, MAX_NOTIFICATIONS>,
+}
+
+impl Notifications {
+    pub fn new() -> Self {
+        Notifications {
+            pending: Vec::new(),
+        }
+    }
+
+    /// A repeat of a pending notification's source replaces it
+    pub fn push(&mut self, notification: Notification) {
+        if let Some(pending) = self
+            .pending
+            .iter_mut()
+            .find(|n| n.cause.same_source(&notification.cause))
+        {
+            *pending = notification;
+            return;
+        }
+        if self.pending.is_full() {
+            // The oldest of the lowest priority
+            let lowest = self
+                .pending
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, n)| (n.cause.priority(), n.datetime))
+                .map(|(i, n)| (i, n.cause.priority()));
+            match lowest {
+                Some((i, priority)) if priority <= notification.cause.priority() => {
+                    self.pending.swap_remove(i);
+                }
+                _ => return,
+            }
+        }
+        // Can't fail, there's room
+        let _ = self.pending.push(notification);
+    }
+
+    /// The highest priority, the newest of equal priorities
+    pub fn current(&self) -> Option<&Notification> {
+        self.current_index().map(|i| &self.pending[i])
+    }
+
+    /// Removes the current notification, returns it
+    pub fn dismiss(&mut self) -> Option<Notification> {
+        let index = self.current_index()?;
+        Some(self.pending.swap_remove(index))
+    }
+
+    fn current_index(&self) -> Option<usize> {
+        self.pending
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, n)| (n.cause.priority(), n.datetime))
+            .map(|(i, _)| i)
+    }
+}
+
+impl Default for Notifications {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alarm::{Channel, Comparison};
+    use ds323x::NaiveDate;
+    use embedded_time::duration::Seconds;
+
+    fn notification(cause: Cause, minute: u32) -> Notification {
+        Notification {
+            cause,
+            datetime: NaiveDate::from_ymd(2021, 6, 1).and_hms(12, minute, 0),
+        }
+    }
+
+    fn alarm(threshold: f32, severity: Severity, minute: u32) -> Notification {
+        let rule = AlarmRule {
+            channel: Channel::Temperature,
+            comparison: Comparison::Above(threshold),
+            severity,
+            hysteresis: 0.0,
+            trigger_delay: Seconds(0),
+            min_on_time: Seconds(0),
+            rate_window: None,
+        };
+        notification(
+            Cause::Alarm {
+                rule,
+                value: Some(threshold + 1.0),
+            },
+            minute,
+        )
+    }
+
+    /// Dismisses every pending notification, highest priority first
+    fn drain(notifications: &mut Notifications) -> Vec<Notification, MAX_NOTIFICATIONS> {
+        let mut dismissed = Vec::new();
+        while let Some(notification) = notifications.dismiss() {
+            dismissed.push(notification).unwrap();
+        }
+        dismissed
+    }
+
+    #[test]
+    fn repeats_replace_the_pending_notification() {
+        let mut notifications = Notifications::new();
+        notifications.push(alarm(30.0, Severity::Warning, 0));
+        notifications.push(notification(Cause::SensorFailure, 1));
+        notifications.push(alarm(30.0, Severity::Warning, 2));
+        notifications.push(notification(Cause::SensorFailure, 3));
+        notifications.push(alarm(35.0, Severity::Warning, 4));
+        assert_eq!(
+            drain(&mut notifications),
+            [
+                notification(Cause::SensorFailure, 3),
+                alarm(35.0, Severity::Warning, 4),
+                alarm(30.0, Severity::Warning, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn highest_priority_then_newest_first() {
+        let mut notifications = Notifications::new();
+        notifications.push(alarm(20.0, Severity::Info, 0));
+        notifications.push(notification(Cause::StorageFull, 1));
+        notifications.push(alarm(35.0, Severity::Critical, 2));
+        notifications.push(alarm(30.0, Severity::Warning, 3));
+        notifications.push(alarm(25.0, Severity::Info, 4));
+        assert_eq!(
+            notifications.current(),
+            Some(&alarm(35.0, Severity::Critical, 2))
+        );
+        assert_eq!(
+            drain(&mut notifications),
+            [
+                alarm(35.0, Severity::Critical, 2),
+                alarm(30.0, Severity::Warning, 3),
+                notification(Cause::StorageFull, 1),
+                alarm(25.0, Severity::Info, 4),
+                alarm(20.0, Severity::Info, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn dismiss_removes_the_current_notification() {
+        let mut notifications = Notifications::new();
+        assert_eq!(notifications.current(), None);
+        assert_eq!(notifications.dismiss(), None);
+        notifications.push(notification(Cause::StorageError, 0));
+        notifications.push(notification(Cause::SensorFailure, 1));
+        assert_eq!(
+            notifications.dismiss(),
+            Some(notification(Cause::SensorFailure, 1))
+        );
+        assert_eq!(
+            notifications.current(),
+            Some(&notification(Cause::StorageError, 0))
+        );
+        assert_eq!(
+            notifications.dismiss(),
+            Some(notification(Cause::StorageError, 0))
+        );
+        assert_eq!(notifications.current(), None);
+    }
+
+    #[test]
+    fn full_queue_drops_the_oldest_lowest_priority() {
+        let mut notifications = Notifications::new();
+        for i in 0..MAX_NOTIFICATIONS as u32 {
+            let severity = if i == 3 {
+                Severity::Critical
+            } else {
+                Severity::Info
+            };
+            notifications.push(alarm(20.0 + i as f32, severity, i));
+        }
+        // Evicts the info alarm of minute 0
+        notifications.push(alarm(40.0, Severity::Warning, 10));
+        // Evicts the info alarm of minute 1, equal priority
+        notifications.push(alarm(41.0, Severity::Info, 11));
+        let dismissed = drain(&mut notifications);
+        assert_eq!(dismissed.len(), MAX_NOTIFICATIONS);
+        assert_eq!(dismissed[0], alarm(23.0, Severity::Critical, 3));
+        assert_eq!(dismissed[1], alarm(40.0, Severity::Warning, 10));
+        assert_eq!(dismissed[2], alarm(41.0, Severity::Info, 11));
+        assert_eq!(
+            dismissed[MAX_NOTIFICATIONS - 1],
+            alarm(22.0, Severity::Info, 2)
+        );
+    }
+
+    #[test]
+    fn full_queue_drops_a_lower_priority_newcomer() {
+        let mut notifications = Notifications::new();
+        for i in 0..MAX_NOTIFICATIONS as u32 {
+            notifications.push(alarm(20.0 + i as f32, Severity::Warning, i));
+        }
+        notifications.push(alarm(40.0, Severity::Info, 10));
+        let dismissed = drain(&mut notifications);
+        assert_eq!(dismissed.len(), MAX_NOTIFICATIONS);
+        assert!(dismissed
+            .iter()
+            .all(|n| n.cause.priority() == Severity::Warning));
+    }
+}
@@ -0,0 +1,177 @@
+//! Psychrometric values derived from temperature and relative humidity
+
+use crate::sensor::Measurement;
+use crate::util;
+use micromath::F32Ext;
+
+// Magnus formula coefficients (Alduchov and Eskridge), valid over [-45, 60] °C
+const MAGNUS_A: f32 = 6.1094;
+const MAGNUS_B: f32 = 17.625;
+const MAGNUS_C: f32 = 243.04;
+
+const KELVIN_OFFSET: f32 = 273.15;
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug, Default)]
+pub struct Psychrometrics {
+    /// Dew point in degree celsius (°C)
+    pub dew_point: f32,
+
+    /// Absolute humidity in grams of water vapor per cubic meter (g/m³)
+    pub absolute_humidity: f32,
+
+    /// Heat index in degree celsius (°C)
+    pub heat_index: f32,
+
+    /// Humidex, dimensionless (reads like °C)
+    pub humidex: f32,
+}
+
+impl Psychrometrics {
+    pub fn new(temp_c: f32, humidity: f32) -> Self {
+        let dew_point = dew_point(temp_c, humidity);
+        Psychrometrics {
+            dew_point,
+            absolute_humidity: absolute_humidity(temp_c, humidity),
+            heat_index: heat_index(temp_c, humidity),
+            humidex: humidex(temp_c, dew_point),
+        }
+    }
+}
+
+impl From<&Measurement> for Psychrometrics {
+    fn from(data: &Measurement) -> Self {
+        Psychrometrics::new(data.temperature, data.humidity)
+    }
+}
+
+/// Saturation vapor pressure in hectopascal (hPa) over water
+pub fn saturation_vapor_pressure(temp_c: f32) -> f32 {
+    MAGNUS_A * ((MAGNUS_B * temp_c) / (MAGNUS_C + temp_c)).exp()
+}
+
+/// Dew point in degree celsius (°C), Magnus formula
+pub fn dew_point(temp_c: f32, humidity: f32) -> f32 {
+    // ln(0) is undefined, the sensor never reports a truly dry room anyway
+    let rh = humidity.clamp(1.0, 100.0) / 100.0;
+    let gamma = rh.ln() + (MAGNUS_B * temp_c) / (MAGNUS_C + temp_c);
+    (MAGNUS_C * gamma) / (MAGNUS_B - gamma)
+}
+
+/// Absolute humidity in grams of water vapor per cubic meter (g/m³)
+pub fn absolute_humidity(temp_c: f32, humidity: f32) -> f32 {
+    // Ideal gas law with the specific gas constant of water vapor,
+    // 100 / 461.5 J/(kg·K) * 1000 g/kg = 216.7
+    let vapor_pressure = saturation_vapor_pressure(temp_c) * humidity.clamp(0.0, 100.0) / 100.0;
+    216.7 * vapor_pressure / (temp_c + KELVIN_OFFSET)
+}
+
+/// Heat index in degree celsius (°C), NWS Rothfusz regression
+pub fn heat_index(temp_c: f32, humidity: f32) -> f32 {
+    let t = util::celsius_to_fahrenheit(temp_c);
+    let rh = humidity.clamp(0.0, 100.0);
+
+    // Steadman's simple formula is used below ~80 °F, where the regression is invalid
+    let simple = 0.5 * (t + 61.0 + ((t - 68.0) * 1.2) + (rh * 0.094));
+    let hi = if ((simple + t) / 2.0) < 80.0 {
+        simple
+    } else {
+        let mut hi = -42.379 + (2.049_015_3 * t) + (10.143_331 * rh)
+            - (0.224_755_4 * t * rh)
+            - (0.006_837_83 * t * t)
+            - (0.054_817_17 * rh * rh)
+            + (0.001_228_74 * t * t * rh)
+            + (0.000_852_82 * t * rh * rh)
+            - (0.000_001_99 * t * t * rh * rh);
+        if rh < 13.0 && (80.0..=112.0).contains(&t) {
+            hi -= ((13.0 - rh) / 4.0) * ((17.0 - (t - 95.0).abs()) / 17.0).sqrt();
+        } else if rh > 85.0 && (80.0..=87.0).contains(&t) {
+            hi += ((rh - 85.0) / 10.0) * ((87.0 - t) / 5.0);
+        }
+        hi
+    };
+
+    util::fahrenheit_to_celsius(hi)
+}
+
+/// Humidex (Environment Canada) from the temperature and dew point in °C
+pub fn humidex(temp_c: f32, dew_point_c: f32) -> f32 {
+    let vapor_pressure =
+        6.11 * (5417.753 * ((1.0 / 273.16) - (1.0 / (dew_point_c + KELVIN_OFFSET)))).exp();
+    temp_c + (0.5555 * (vapor_pressure - 10.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use micromath::F32Ext;
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn dew_point_reference_values() {
+        // NOAA dew point calculator
+        assert_close(dew_point(25.0, 50.0), 13.9, 0.1);
+        assert_close(dew_point(0.0, 80.0), -3.0, 0.1);
+        assert_close(dew_point(30.0, 70.0), 23.9, 0.1);
+        // Saturated air is at its dew point
+        assert_close(dew_point(20.0, 100.0), 20.0, 0.1);
+    }
+
+    #[test]
+    fn dew_point_of_dry_air_is_finite() {
+        assert!(dew_point(20.0, 0.0).is_finite());
+    }
+
+    #[test]
+    fn absolute_humidity_reference_values() {
+        assert_close(absolute_humidity(25.0, 100.0), 23.0, 0.2);
+        assert_close(absolute_humidity(20.0, 50.0), 8.6, 0.1);
+        assert_close(absolute_humidity(0.0, 100.0), 4.85, 0.1);
+        assert_close(absolute_humidity(20.0, 0.0), 0.0, 0.001);
+    }
+
+    #[test]
+    fn heat_index_reference_values() {
+        // NWS heat index chart, °F converted
+        assert_close(
+            heat_index(util::fahrenheit_to_celsius(90.0), 70.0),
+            41.1,
+            0.3,
+        );
+        assert_close(
+            heat_index(util::fahrenheit_to_celsius(100.0), 40.0),
+            42.8,
+            0.3,
+        );
+        assert_close(
+            heat_index(util::fahrenheit_to_celsius(86.0), 90.0),
+            40.6,
+            0.3,
+        );
+        // Steadman's formula below 80 °F
+        assert_close(heat_index(20.0, 50.0), 19.4, 0.1);
+    }
+
+    #[test]
+    fn humidex_reference_values() {
+        // Environment Canada humidex table, (temperature, dew point) -> humidex
+        assert_close(humidex(30.0, 15.0), 34.0, 0.5);
+        assert_close(humidex(25.0, 20.0), 33.0, 0.5);
+        assert_close(humidex(35.0, 25.0), 47.0, 0.5);
+    }
+
+    #[test]
+    fn psychrometrics_humidex_uses_the_dew_point() {
+        let p = Psychrometrics::new(30.0, 70.0);
+        assert_close(p.dew_point, dew_point(30.0, 70.0), 0.001);
+        assert_close(p.humidex, humidex(30.0, p.dew_point), 0.001);
+    }
+}
@@ -0,0 +1,147 @@
+//! Rate of change of a channel, for derivative alarm rules
+
+use crate::ring_buffer::RingBuffer;
+use crate::system_clock::SystemClock;
+use embedded_time::{
+    duration::{Minutes, Seconds},
+    Instant,
+};
+
+const SAMPLE_INTERVAL: Seconds = Seconds(60_u32);
+
+/// Longest supported rate window
+pub const MAX_WINDOW: Minutes = Minutes(180_u32);
+
+/// Each end of the window is the median of this many samples, so a single
+/// noisy sample can't trigger a rate alarm
+const MEDIAN_LEN: usize = 5;
+
+const HISTORY_LEN: usize = MAX_WINDOW.0 as usize + MEDIAN_LEN;
+
+/// Samples a channel once a minute
+pub struct RateHistory {
+    history: RingBuffer<f32, HISTORY_LEN>,
+    next_sample: Option<Instant<SystemClock>>,
+}
+
+impl RateHistory {
+    pub fn new() -> Self {
+        RateHistory {
+            history: RingBuffer::new(),
+            next_sample: None,
+        }
+    }
+
+    pub fn update(&mut self, now: &Instant<SystemClock>, value: f32) {
+        let next_sample = match self.next_sample {
+            None => *now,
+            Some(next) => next,
+        };
+        let dur = match now.checked_duration_since(&next_sample) {
+            Some(dur) => dur,
+            // Not due yet
+            None => return,
+        };
+        if dur >= Seconds(2 * SAMPLE_INTERVAL.0).into() {
+            // Missed samples (e.g. the sensor failed), the spacing would be wrong
+            self.history.clear();
+            self.next_sample = now.checked_add(SAMPLE_INTERVAL);
+        } else {
+            // Scheduled from the previous sample, the sensor polling jitter
+            // doesn't accumulate
+            self.next_sample = next_sample.checked_add(SAMPLE_INTERVAL);
+        }
+        self.history.push(value);
+    }
+
+    /// Change over the window, in the channel's units per window, once
+    /// enough history is available
+    pub fn rate(&self, window: Minutes) -> Option<f32> {
+        let window = window.0.clamp(1, MAX_WINDOW.0) as usize;
+        let len = self.history.len();
+        if len < window + MEDIAN_LEN {
+            return None;
+        }
+        let newest = self.median(len - MEDIAN_LEN);
+        let oldest = self.median(len - MEDIAN_LEN - window);
+        Some(newest - oldest)
+    }
+
+    /// Median of the MEDIAN_LEN samples starting at index (0 = oldest)
+    fn median(&self, start: usize) -> f32 {
+        let mut sorted = [0.0_f32; MEDIAN_LEN];
+        for (i, dst) in sorted.iter_mut().enumerate() {
+            *dst = self.history.get(start + i).unwrap_or_default();
+        }
+        sorted.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap_or(core::cmp::Ordering::Equal));
+        sorted[MEDIAN_LEN / 2]
+    }
+}
+
+impl Default for RateHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(sec: u32) -> Instant<SystemClock> {
+        Instant::new(sec)
+    }
+
+    /// One sample a minute from `values`
+    fn history(values: &[f32]) -> RateHistory {
+        let mut history = RateHistory::new();
+        for (minute, value) in values.iter().enumerate() {
+            history.update(&at(minute as u32 * 60), *value);
+        }
+        history
+    }
+
+    #[test]
+    fn rate_over_the_window() {
+        let mut values = [0.0; 20];
+        for (minute, value) in values.iter_mut().enumerate() {
+            *value = 20.0 + (0.5 * minute as f32);
+        }
+        let history = history(&values);
+        assert_eq!(history.rate(Minutes(10)), Some(5.0));
+        assert_eq!(history.rate(Minutes(15)), Some(7.5));
+        // Needs the window plus a median's worth of samples
+        assert_eq!(history.rate(Minutes(16)), None);
+    }
+
+    #[test]
+    fn samples_once_a_minute() {
+        let mut history = RateHistory::new();
+        for sec in (0..=300).step_by(15) {
+            history.update(&at(sec), sec as f32);
+        }
+        assert_eq!(history.history.len(), 6);
+        assert_eq!(history.rate(Minutes(1)), Some(60.0));
+    }
+
+    #[test]
+    fn missed_samples_restart_the_history() {
+        let mut history = history(&[20.0; 10]);
+        assert_eq!(history.rate(Minutes(5)), Some(0.0));
+        history.update(&at(15 * 60), 25.0);
+        assert_eq!(history.history.len(), 1);
+        assert_eq!(history.rate(Minutes(1)), None);
+    }
+
+    #[test]
+    fn single_sample_spike_is_ignored() {
+        let mut values = [20.0; 12];
+        for spike in 0..values.len() {
+            values[spike] = 30.0;
+            let history = history(&values);
+            assert_eq!(history.rate(Minutes(1)), Some(0.0), "spike at {}", spike);
+            assert_eq!(history.rate(Minutes(6)), Some(0.0), "spike at {}", spike);
+            values[spike] = 20.0;
+        }
+    }
+}
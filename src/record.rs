@@ -1,12 +1,21 @@
-use crate::util;
-use bme680::FieldData;
+use crate::actuator::{Actuator, MAX_ACTUATORS};
+use crate::alarm::Channel;
+use crate::barometer;
+use crate::calibration::Calibration;
+use crate::compensation::SelfHeating;
+use crate::config;
+use crate::psychro::Psychrometrics;
+use crate::sensor::Measurement;
+use crate::units::Units;
+use crate::warm_up::Quantity;
 use core::fmt::Write;
 use ds323x::{Datelike, NaiveDateTime, Timelike};
-use heapless::String;
+use heapless::{String, Vec};
 
 // TODO - if these get big, put them in the bss section instead of on the stack
 const TIMESTAMP_STRING_CAP: usize = 32;
-const CSV_LINE_STRING_CAP: usize = TIMESTAMP_STRING_CAP + (4 * 16);
+const CSV_LINE_STRING_CAP: usize = TIMESTAMP_STRING_CAP + (14 * 16) + (2 * MAX_ACTUATORS);
+const CSV_HEADER_STRING_CAP: usize = 384;
 
 #[derive(Debug, err_derive::Error)]
 pub enum Error {
@@ -21,24 +30,58 @@ pub struct Record {
     /// YYYY-MM-DDThh:mm:ss
     pub timestamp: String<TIMESTAMP_STRING_CAP>,
 
-    /// Temperature in degree fahrenheit (°F)
+    /// Temperature in the configured unit
     pub temperature: f32,
 
     /// Humidity in % relative humidity
     pub humidity: f32,
 
-    /// Pressure in hectopascal (hPA)
+    /// Pressure in the configured unit
     pub pressure: f32,
 
-    /// Gas resistance, present if the valid bit is set on the BME689
+    /// Gas resistance in the configured unit, present if the valid bit is
+    /// set on the BME689
     /// If not valid, value 0 is used
-    pub gas_resistance: Option<u32>,
+    pub gas_resistance: Option<f32>,
+
+    /// The quantities still warming up, see config::WARM_UP
+    pub settling: Vec<Quantity, 4>,
+
+    /// State of each configured actuator, in config::ACTUATORS order
+    pub actuators: Vec<bool, MAX_ACTUATORS>,
+
+    /// Derived values, present if config::RECORD_PSYCHROMETRICS is set
+    /// Temperatures are in the configured unit
+    pub psychrometrics: Option<Psychrometrics>,
+
+    /// Sea-level pressure in the configured unit, present if
+    /// config::RECORD_SEA_LEVEL_PRESSURE is set
+    pub sea_level_pressure: Option<f32>,
+
+    /// Self-heating model inputs and correction term, present if
+    /// config::RECORD_SELF_HEATING is set
+    /// Temperatures are in the configured unit, the correction is in °C
+    pub self_heating: Option<SelfHeating>,
+
+    /// Units of the values above
+    pub units: Units,
+
+    /// No readings, the sensor has failed. The values are written as empty
+    /// fields, the record only carries the actuator states.
+    pub sensor_failure: bool,
 }
 
 // TODO - probably don't need to have intermediate state, just convert to csv
 // string
 impl Record {
-    pub fn new(datetime: &NaiveDateTime, data: &FieldData) -> Result<Self, Error> {
+    pub fn new(
+        datetime: &NaiveDateTime,
+        data: &Measurement,
+        self_heating: &SelfHeating,
+        settling: &[Quantity],
+        actuators: &[Option<Actuator>],
+        units: &Units,
+    ) -> Result<Self, Error> {
         let mut timestamp = String::new();
 
         let date = datetime.date();
@@ -57,30 +100,210 @@ impl Record {
 
         Ok(Record {
             timestamp,
-            temperature: util::celsius_to_fahrenheit(data.temperature_celsius()),
-            humidity: data.humidity_percent(),
-            pressure: data.pressure_hpa(),
-            gas_resistance: if data.gas_valid() {
-                data.gas_resistance_ohm().into()
+            temperature: units.convert(Channel::Temperature, data.temperature),
+            humidity: data.humidity,
+            pressure: units.convert(Channel::Pressure, data.pressure),
+            gas_resistance: data
+                .gas_resistance
+                .map(|r| units.convert(Channel::GasResistance, r as f32)),
+            settling: settling.iter().copied().collect(),
+            actuators: actuators.iter().flatten().map(Actuator::is_on).collect(),
+            psychrometrics: if config::RECORD_PSYCHROMETRICS {
+                let p = Psychrometrics::from(data);
+                Some(Psychrometrics {
+                    dew_point: units.convert(Channel::DewPoint, p.dew_point),
+                    heat_index: units.convert(Channel::HeatIndex, p.heat_index),
+                    ..p
+                })
+            } else {
+                None
+            },
+            sea_level_pressure: if config::RECORD_SEA_LEVEL_PRESSURE {
+                let slp = barometer::sea_level_pressure(
+                    data.pressure,
+                    config::STATION_ALTITUDE_M,
+                    data.temperature,
+                );
+                Some(units.convert(Channel::SeaLevelPressure, slp))
             } else {
                 None
             },
+            self_heating: if config::RECORD_SELF_HEATING {
+                let mut s = *self_heating;
+                s.sources.mcu_temperature =
+                    units.convert(Channel::Temperature, s.sources.mcu_temperature);
+                s.sources.rtc_temperature =
+                    units.convert(Channel::Temperature, s.sources.rtc_temperature);
+                Some(s)
+            } else {
+                None
+            },
+            units: *units,
+            sensor_failure: false,
         })
     }
 
+    /// A record of the actuators switched off by a sensor failure, there
+    /// are no readings to record with them
+    pub fn sensor_failure(
+        datetime: &NaiveDateTime,
+        actuators: &[Option<Actuator>],
+        units: &Units,
+    ) -> Result<Self, Error> {
+        let record = Record::new(
+            datetime,
+            &Measurement::default(),
+            &SelfHeating::default(),
+            &[],
+            actuators,
+            units,
+        )?;
+        Ok(Record {
+            sensor_failure: true,
+            ..record
+        })
+    }
+
+    /// Active calibration, units and column names, written as comment lines
+    /// whenever storage is initialized
+    pub fn csv_header(
+        calibration: &Calibration,
+        units: &Units,
+    ) -> Result<String<CSV_HEADER_STRING_CAP>, Error> {
+        let mut s = String::new();
+        writeln!(&mut s, "#calibration {}", calibration).map_err(|_| Error::StringFormatting)?;
+        writeln!(&mut s, "#units {}", units).map_err(|_| Error::StringFormatting)?;
+        write!(
+            &mut s,
+            "#timestamp,temperature,humidity,pressure,gas_resistance,settling"
+        )
+        .map_err(|_| Error::StringFormatting)?;
+        if config::RECORD_PSYCHROMETRICS {
+            write!(&mut s, ",dew_point,absolute_humidity,heat_index,humidex")
+                .map_err(|_| Error::StringFormatting)?;
+        }
+        if config::RECORD_SEA_LEVEL_PRESSURE {
+            write!(&mut s, ",sea_level_pressure").map_err(|_| Error::StringFormatting)?;
+        }
+        if config::RECORD_SELF_HEATING {
+            write!(
+                &mut s,
+                ",mcu_temperature,rtc_temperature,brightness,uptime,self_heating_correction"
+            )
+            .map_err(|_| Error::StringFormatting)?;
+        }
+        for actuator in config::ACTUATORS.iter().flatten() {
+            write!(&mut s, ",{}", actuator.name).map_err(|_| Error::StringFormatting)?;
+        }
+        writeln!(&mut s).map_err(|_| Error::StringFormatting)?;
+        Ok(s)
+    }
+
     // TODO - maybe newline here
     pub fn to_csv_line(&self) -> Result<String<CSV_LINE_STRING_CAP>, Error> {
         let mut s = String::new();
-        writeln!(
+        if self.sensor_failure {
+            write!(&mut s, "{},,,,,", self.timestamp).map_err(|_| Error::StringFormatting)?;
+            let optional_columns = [
+                (config::RECORD_PSYCHROMETRICS, 4),
+                (config::RECORD_SEA_LEVEL_PRESSURE, 1),
+                (config::RECORD_SELF_HEATING, 5),
+            ];
+            for (enabled, columns) in optional_columns.iter() {
+                if *enabled {
+                    for _ in 0..*columns {
+                        s.push(',').map_err(|_| Error::StringFormatting)?;
+                    }
+                }
+            }
+            self.write_actuators(&mut s)?;
+            return Ok(s);
+        }
+        write!(
             &mut s,
-            "{},{:.2},{:.2},{:.2},{}",
+            "{},{:.2},{:.2},{:.*},{:.*},",
             self.timestamp,
             self.temperature,
             self.humidity,
+            self.units.decimals(Channel::Pressure),
             self.pressure,
-            self.gas_resistance.unwrap_or(0)
+            self.units.decimals(Channel::GasResistance),
+            self.gas_resistance.unwrap_or(0.0),
         )
         .map_err(|_| Error::StringFormatting)?;
+        // The symbols of the quantities still settling, empty once all have
+        for quantity in self.settling.iter() {
+            s.push(quantity.symbol())
+                .map_err(|_| Error::StringFormatting)?;
+        }
+        if let Some(p) = &self.psychrometrics {
+            write!(
+                &mut s,
+                ",{:.2},{:.2},{:.2},{:.2}",
+                p.dew_point, p.absolute_humidity, p.heat_index, p.humidex
+            )
+            .map_err(|_| Error::StringFormatting)?;
+        }
+        if let Some(p) = self.sea_level_pressure {
+            let decimals = self.units.decimals(Channel::SeaLevelPressure);
+            write!(&mut s, ",{:.*}", decimals, p).map_err(|_| Error::StringFormatting)?;
+        }
+        if let Some(h) = &self.self_heating {
+            write!(
+                &mut s,
+                ",{:.2},{:.2},{:.2},{},{:.3}",
+                h.sources.mcu_temperature,
+                h.sources.rtc_temperature,
+                h.sources.brightness,
+                h.sources.uptime_sec,
+                h.correction
+            )
+            .map_err(|_| Error::StringFormatting)?;
+        }
+        self.write_actuators(&mut s)?;
         Ok(s)
     }
+
+    /// The actuator columns and the line ending
+    fn write_actuators(&self, s: &mut String<CSV_LINE_STRING_CAP>) -> Result<(), Error> {
+        for on in self.actuators.iter() {
+            write!(s, ",{}", u8::from(*on)).map_err(|_| Error::StringFormatting)?;
+        }
+        writeln!(s).map_err(|_| Error::StringFormatting)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ds323x::NaiveDate;
+
+    fn column_count(line: &str) -> usize {
+        line.trim_end().split(',').count()
+    }
+
+    #[test]
+    fn sensor_failure_record_matches_the_header() {
+        let units = config::UNITS;
+        let header = Record::csv_header(&Calibration::default(), &units).unwrap();
+        let columns = header.lines().last().map(column_count).unwrap();
+        let dt = NaiveDate::from_ymd(2021, 6, 1).and_hms(12, 30, 0);
+        let actuators = config::ACTUATORS.map(|a| a.map(Actuator::new));
+
+        let record = Record::new(
+            &dt,
+            &Measurement::default(),
+            &SelfHeating::default(),
+            &[],
+            &actuators,
+            &units,
+        )
+        .unwrap();
+        assert_eq!(column_count(&record.to_csv_line().unwrap()), columns);
+
+        let failure = Record::sensor_failure(&dt, &actuators, &units).unwrap();
+        let line = failure.to_csv_line().unwrap();
+        assert_eq!(column_count(&line), columns);
+        assert!(line.starts_with("2021-06-01T12:30:00,,,,,"));
+    }
 }
@@ -0,0 +1,81 @@
+/// Fixed capacity buffer, pushing when full overwrites the oldest element
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct RingBuffer<T, const N: usize> {
+    buf: [T; N],
+    /// Index of the next write
+    head: usize,
+    len: usize,
+}
+
+impl<T, const N: usize> RingBuffer<T, N>
+where
+    T: Copy + Default,
+{
+    pub fn new() -> Self {
+        RingBuffer {
+            buf: [T::default(); N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    pub fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.buf[self.head] = item;
+        self.head = (self.head + 1) % N;
+        if self.len < N {
+            self.len += 1;
+        }
+    }
+
+    /// Index 0 is the oldest element
+    pub fn get(&self, index: usize) -> Option<T> {
+        if index < self.len {
+            Some(self.buf[(self.head + N - self.len + index) % N])
+        } else {
+            None
+        }
+    }
+
+    pub fn oldest(&self) -> Option<T> {
+        self.get(0)
+    }
+
+    pub fn newest(&self) -> Option<T> {
+        self.len.checked_sub(1).and_then(|i| self.get(i))
+    }
+
+    /// Iterate from oldest to newest
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = T> + '_ {
+        (0..self.len).filter_map(move |i| self.get(i))
+    }
+}
+
+impl<T, const N: usize> Default for RingBuffer<T, N>
+where
+    T: Copy + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,5 +1,5 @@
-use crate::hal::hal::blocking::i2c::{Write, WriteRead};
 use ds323x::{ic::DS3231, interface::I2cInterface, Ds323x, Error, NaiveDateTime, Rtcc};
+use embedded_hal::blocking::i2c::{Write, WriteRead};
 
 pub struct Rtc<I2C> {
     drv: Ds323x<I2cInterface<I2C>, DS3231>,
@@ -23,4 +23,9 @@ where
     pub fn get_datetime(&mut self) -> Result<NaiveDateTime, Error<E, ()>> {
         self.drv.get_datetime()
     }
+
+    /// Temperature in degree celsius (°C), updated by the DS3231 every 64 seconds
+    pub fn get_temperature(&mut self) -> Result<f32, Error<E, ()>> {
+        self.drv.get_temperature()
+    }
 }
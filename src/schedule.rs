@@ -0,0 +1,325 @@
+//! Time-of-day schedules, evaluated against the RTC time
+
+use crate::sun::Location;
+use chrono::Weekday;
+use ds323x::{Datelike, NaiveDateTime, NaiveTime, Timelike};
+use embedded_time::duration::Minutes;
+
+const MINUTES_PER_DAY: f32 = 24.0 * 60.0;
+
+/// Minutes after midnight used for sunrise and sunset relative levels when
+/// the sun doesn't rise or set that day
+const POLAR_SUNRISE_MIN: f32 = 6.0 * 60.0;
+const POLAR_SUNSET_MIN: f32 = 18.0 * 60.0;
+
+/// Daily window from start (inclusive) to end (exclusive), wraps past
+/// midnight when end is before start
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct TimeWindow {
+    /// Minutes since midnight
+    start: u16,
+    end: u16,
+}
+
+impl TimeWindow {
+    pub const fn new(start_hour: u8, start_minute: u8, end_hour: u8, end_minute: u8) -> Self {
+        TimeWindow {
+            start: (start_hour as u16 * 60) + start_minute as u16,
+            end: (end_hour as u16 * 60) + end_minute as u16,
+        }
+    }
+
+    pub fn contains(&self, time: &NaiveTime) -> bool {
+        self.contains_from_start(time) || self.contains_after_midnight(time)
+    }
+
+    /// The part of the window on the day it starts
+    fn contains_from_start(&self, time: &NaiveTime) -> bool {
+        let minute = minute_of_day(time);
+        if self.start <= self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start
+        }
+    }
+
+    /// The part of a window that wraps past midnight on the following day
+    fn contains_after_midnight(&self, time: &NaiveTime) -> bool {
+        self.start > self.end && minute_of_day(time) < self.end
+    }
+}
+
+fn minute_of_day(time: &NaiveTime) -> u16 {
+    (time.hour() * 60 + time.minute()) as u16
+}
+
+/// Separate windows for weekdays and weekends (Saturday, Sunday), None for
+/// no window on those days. A window belongs to the day it starts on, so a
+/// weekday window that wraps past midnight on Friday night continues into
+/// Saturday morning and Sunday's window into Monday morning.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct WeeklySchedule {
+    pub weekday: Option<TimeWindow>,
+    pub weekend: Option<TimeWindow>,
+}
+
+impl WeeklySchedule {
+    pub fn contains(&self, datetime: &NaiveDateTime) -> bool {
+        let time = datetime.time();
+        let today = self.window(datetime.weekday());
+        let yesterday = self.window(datetime.weekday().pred());
+        today.map_or(false, |w| w.contains_from_start(&time))
+            || yesterday.map_or(false, |w| w.contains_after_midnight(&time))
+    }
+
+    fn window(&self, weekday: Weekday) -> Option<TimeWindow> {
+        match weekday {
+            Weekday::Sat | Weekday::Sun => self.weekend,
+            _ => self.weekday,
+        }
+    }
+}
+
+/// Time of day, either fixed or relative to sunrise or sunset in minutes
+/// (negative for before)
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum TimeOfDay {
+    At { hour: u8, minute: u8 },
+    Sunrise(i16),
+    Sunset(i16),
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct BrightnessLevel {
+    pub time: TimeOfDay,
+    /// SSD1306 contrast, 0 (dimmest) ..= 255 (brightest)
+    pub contrast: u8,
+}
+
+/// The most recent level applies, fading in from the level before it over
+/// the fade duration
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct DimmingSchedule {
+    pub levels: &'static [BrightnessLevel],
+    pub fade: Minutes,
+}
+
+impl DimmingSchedule {
+    /// Brightest without any levels
+    pub fn contrast(&self, datetime: &NaiveDateTime, location: &Location) -> u8 {
+        let time = datetime.time();
+        let now = (time.hour() * 60 + time.minute()) as f32 + (time.second() as f32 / 60.0);
+        let (sunrise, sunset) = location
+            .sunrise_sunset(&datetime.date())
+            .unwrap_or((POLAR_SUNRISE_MIN, POLAR_SUNSET_MIN));
+
+        // (minutes since, contrast) of the most recent level and the one before
+        let mut current: Option<(f32, u8)> = None;
+        let mut previous: Option<(f32, u8)> = None;
+        for level in self.levels.iter() {
+            let at = match level.time {
+                TimeOfDay::At { hour, minute } => f32::from(hour) * 60.0 + f32::from(minute),
+                TimeOfDay::Sunrise(offset) => sunrise + f32::from(offset),
+                TimeOfDay::Sunset(offset) => sunset + f32::from(offset),
+            };
+            let mut since = (now - at) % MINUTES_PER_DAY;
+            if since < 0.0 {
+                since += MINUTES_PER_DAY;
+            }
+            let entry = Some((since, level.contrast));
+            match current {
+                Some((current_since, _)) if since >= current_since => {
+                    if previous.map_or(true, |(previous_since, _)| since < previous_since) {
+                        previous = entry;
+                    }
+                }
+                _ => {
+                    previous = current;
+                    current = entry;
+                }
+            }
+        }
+
+        let (since, contrast) = match current {
+            Some(current) => current,
+            None => return u8::MAX,
+        };
+        let from = previous.map_or(contrast, |(_, contrast)| contrast);
+        let fraction = if self.fade.0 != 0 {
+            (since / self.fade.0 as f32).min(1.0)
+        } else {
+            1.0
+        };
+        let contrast = f32::from(from) + ((f32::from(contrast) - f32::from(from)) * fraction);
+        (contrast + 0.5).clamp(0.0, 255.0) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config;
+    use ds323x::NaiveDate;
+
+    fn contrast_at(hour: u32, minute: u32) -> u8 {
+        let datetime = NaiveDate::from_ymd(2021, 12, 21).and_hms(hour, minute, 0);
+        config::DIMMING.contrast(&datetime, &config::LOCATION)
+    }
+
+    const QUIET_HOURS: WeeklySchedule = WeeklySchedule {
+        weekday: Some(TimeWindow::new(22, 0, 7, 0)),
+        weekend: Some(TimeWindow::new(23, 0, 9, 0)),
+    };
+
+    /// December 2021, the 17th is a Friday
+    fn quiet_at(schedule: &WeeklySchedule, day: u32, hour: u32, minute: u32) -> bool {
+        schedule.contains(&NaiveDate::from_ymd(2021, 12, day).and_hms(hour, minute, 0))
+    }
+
+    #[test]
+    fn time_window_wraps_past_midnight() {
+        let window = TimeWindow::new(22, 0, 7, 0);
+        let time = |hour, minute| NaiveTime::from_hms(hour, minute, 0);
+        assert!(window.contains(&time(22, 0)));
+        assert!(window.contains(&time(0, 0)));
+        assert!(window.contains(&time(6, 59)));
+        assert!(!window.contains(&time(7, 0)));
+        assert!(!window.contains(&time(21, 59)));
+        let window = TimeWindow::new(9, 0, 17, 30);
+        assert!(window.contains(&time(9, 0)));
+        assert!(window.contains(&time(17, 29)));
+        assert!(!window.contains(&time(17, 30)));
+        assert!(!window.contains(&time(8, 59)));
+    }
+
+    #[test]
+    fn friday_night_continues_into_saturday_morning() {
+        let schedule = QUIET_HOURS;
+        assert!(!quiet_at(&schedule, 17, 21, 59));
+        assert!(quiet_at(&schedule, 17, 22, 0));
+        assert!(quiet_at(&schedule, 18, 0, 0));
+        assert!(quiet_at(&schedule, 18, 6, 59));
+        assert!(!quiet_at(&schedule, 18, 7, 0));
+        // Not the morning part of the weekend window
+        assert!(!quiet_at(&schedule, 18, 8, 0));
+        assert!(!quiet_at(&schedule, 18, 22, 30));
+        assert!(quiet_at(&schedule, 18, 23, 0));
+    }
+
+    #[test]
+    fn sunday_night_continues_into_monday_morning() {
+        let schedule = QUIET_HOURS;
+        assert!(quiet_at(&schedule, 19, 8, 59));
+        assert!(!quiet_at(&schedule, 19, 9, 0));
+        assert!(!quiet_at(&schedule, 19, 22, 30));
+        assert!(quiet_at(&schedule, 19, 23, 0));
+        assert!(quiet_at(&schedule, 20, 8, 30));
+        assert!(!quiet_at(&schedule, 20, 9, 0));
+        assert!(quiet_at(&schedule, 20, 22, 0));
+        assert!(!quiet_at(&schedule, 21, 7, 0));
+    }
+
+    #[test]
+    fn a_day_without_a_window_ends_the_previous_one() {
+        let weekdays_only = WeeklySchedule {
+            weekend: None,
+            ..QUIET_HOURS
+        };
+        assert!(quiet_at(&weekdays_only, 18, 3, 0));
+        assert!(!quiet_at(&weekdays_only, 18, 23, 0));
+        assert!(!quiet_at(&weekdays_only, 20, 3, 0));
+        let weekends_only = WeeklySchedule {
+            weekday: None,
+            ..QUIET_HOURS
+        };
+        assert!(!quiet_at(&weekends_only, 18, 3, 0));
+        assert!(quiet_at(&weekends_only, 20, 3, 0));
+        assert!(!quiet_at(&weekends_only, 20, 23, 0));
+    }
+
+    #[test]
+    fn default_quiet_hours_are_off() {
+        for day in 17..24 {
+            for hour in 0..24 {
+                assert!(!quiet_at(&config::QUIET_HOURS, day, hour, 0));
+            }
+        }
+    }
+
+    const FADE_PAST_MIDNIGHT: DimmingSchedule = DimmingSchedule {
+        levels: &[
+            BrightnessLevel {
+                time: TimeOfDay::At {
+                    hour: 12,
+                    minute: 0,
+                },
+                contrast: 255,
+            },
+            BrightnessLevel {
+                time: TimeOfDay::At {
+                    hour: 23,
+                    minute: 0,
+                },
+                contrast: 0,
+            },
+        ],
+        fade: Minutes(120),
+    };
+
+    #[test]
+    fn fade_continues_past_midnight() {
+        let contrast = |day, hour, minute| {
+            let datetime = NaiveDate::from_ymd(2021, 12, day).and_hms(hour, minute, 0);
+            FADE_PAST_MIDNIGHT.contrast(&datetime, &config::LOCATION)
+        };
+        assert_eq!(contrast(21, 22, 59), 255);
+        assert_eq!(contrast(21, 23, 0), 255);
+        assert_eq!(contrast(21, 23, 30), 191);
+        assert_eq!(contrast(22, 0, 0), 128);
+        assert_eq!(contrast(22, 0, 30), 64);
+        assert_eq!(contrast(22, 1, 0), 0);
+        assert_eq!(contrast(22, 11, 59), 0);
+        // And back up from noon
+        assert_eq!(contrast(22, 13, 0), 128);
+    }
+
+    #[test]
+    fn polar_days_fall_back_to_6_am_and_6_pm() {
+        let schedule = DimmingSchedule {
+            levels: &[
+                BrightnessLevel {
+                    time: TimeOfDay::Sunrise(0),
+                    contrast: 255,
+                },
+                BrightnessLevel {
+                    time: TimeOfDay::Sunset(0),
+                    contrast: 0,
+                },
+            ],
+            fade: Minutes(0),
+        };
+        let tromso = Location {
+            latitude: 69.65,
+            longitude: 18.96,
+            utc_offset_min: 60,
+        };
+        let contrast = |hour| {
+            let datetime = NaiveDate::from_ymd(2021, 12, 21).and_hms(hour, 0, 0);
+            schedule.contrast(&datetime, &tromso)
+        };
+        assert_eq!(contrast(5), 0);
+        assert_eq!(contrast(6), 255);
+        assert_eq!(contrast(17), 255);
+        assert_eq!(contrast(18), 0);
+    }
+
+    #[test]
+    fn default_dimming_is_dimmest_from_8_pm_to_8_am() {
+        for (hour, minute) in [(20, 0), (23, 59), (0, 0), (3, 30), (7, 59)] {
+            assert_eq!(contrast_at(hour, minute), 0, "{:02}:{:02}", hour, minute);
+        }
+        for (hour, minute) in [(8, 0), (12, 0), (18, 30), (19, 59)] {
+            assert_eq!(contrast_at(hour, minute), 255, "{:02}:{:02}", hour, minute);
+        }
+    }
+}
@@ -1,14 +1,58 @@
-use crate::hal::hal::blocking::delay::DelayMs;
-use crate::hal::hal::blocking::i2c::{Read, Write};
+use crate::config;
+use crate::i2c_bus::{I2cBus, I2cProxy};
 use crate::system_clock::SystemClock;
+use crate::warm_up::WarmUp;
 use bme680::{
     Bme680, Error, FieldData, FieldDataCondition, I2CAddress, IIRFilterSize, OversamplingSetting,
     PowerMode, SettingsBuilder,
 };
+use core::fmt;
+use embedded_hal::blocking::delay::DelayMs;
+use embedded_hal::blocking::i2c::{Read, Write};
 use embedded_time::{duration::Seconds, Instant};
 
 const POLLING_INTERVAL: Seconds = Seconds(15_u32);
 
+/// Consecutive read failures before the driver is dropped and re-initialized
+const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+
+/// Re-init attempts back off from POLLING_INTERVAL, doubling up to this
+const MAX_RETRY_BACKOFF: Seconds = Seconds(15 * 60_u32);
+
+type DriverError<'a, I2C> =
+    Error<<I2cProxy<'a, I2C> as Read>::Error, <I2cProxy<'a, I2C> as Write>::Error>;
+
+/// Sensor values decoupled from the driver's FieldData, so they can be filtered
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug, Default)]
+pub struct Measurement {
+    /// Temperature in degree celsius (°C)
+    pub temperature: f32,
+
+    /// Humidity in % relative humidity
+    pub humidity: f32,
+
+    /// Pressure in hectopascal (hPa)
+    pub pressure: f32,
+
+    /// Gas resistance in ohms (Ω), present if the valid bit is set
+    pub gas_resistance: Option<u32>,
+}
+
+impl From<&FieldData> for Measurement {
+    fn from(data: &FieldData) -> Self {
+        Measurement {
+            temperature: data.temperature_celsius(),
+            humidity: data.humidity_percent(),
+            pressure: data.pressure_hpa(),
+            gas_resistance: if data.gas_valid() {
+                data.gas_resistance_ohm().into()
+            } else {
+                None
+            },
+        }
+    }
+}
+
 // stm32f4xx Timer only impls Delay<u16>, bme680 wants Delay<u8>
 pub struct DelayWrapper<D: DelayMs<u16>> {
     pub delay: D,
@@ -20,21 +64,222 @@ impl<D: DelayMs<u16>> DelayMs<u8> for DelayWrapper<D> {
     }
 }
 
-pub struct Sensor<I2C, D> {
-    drv: Bme680<I2C, D>,
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum SensorHealth {
+    /// Last read succeeded
+    Ok,
+    /// Recent reads failed, still retrying with the same driver
+    Degraded,
+    /// Driver dropped, waiting to re-initialize
+    Failed,
+}
+
+impl Default for SensorHealth {
+    fn default() -> Self {
+        SensorHealth::Ok
+    }
+}
+
+impl fmt::Display for SensorHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SensorHealth::Ok => f.write_str("OK"),
+            SensorHealth::Degraded => f.write_str("DEG"),
+            SensorHealth::Failed => f.write_str("FAIL"),
+        }
+    }
+}
+
+/// Read failure counting and re-init backoff, kept apart from the driver
+pub struct FailureTracker {
+    initialized: bool,
     last_polled: Instant<SystemClock>,
+    last_init_attempt: Instant<SystemClock>,
+    retry_backoff: Seconds,
+    consecutive_failures: u32,
+    error_count: u32,
+}
+
+impl FailureTracker {
+    /// Starts out uninitialized, with an init attempt made at `now`
+    pub fn new(now: &Instant<SystemClock>) -> Self {
+        FailureTracker {
+            initialized: false,
+            last_polled: *now,
+            last_init_attempt: *now,
+            retry_backoff: POLLING_INTERVAL,
+            consecutive_failures: 0,
+            error_count: 0,
+        }
+    }
+
+    pub fn health(&self) -> SensorHealth {
+        if !self.initialized {
+            SensorHealth::Failed
+        } else if self.consecutive_failures != 0 {
+            SensorHealth::Degraded
+        } else {
+            SensorHealth::Ok
+        }
+    }
+
+    /// Total number of failed reads and init attempts
+    pub fn error_count(&self) -> u32 {
+        self.error_count
+    }
+
+    /// An uninitialized sensor is due for another init attempt
+    pub fn init_due(&self, now: &Instant<SystemClock>) -> bool {
+        !self.initialized
+            && now
+                .checked_duration_since(&self.last_init_attempt)
+                .map_or(false, |dur| dur >= self.retry_backoff.into())
+    }
+
+    pub fn init_succeeded(&mut self, now: &Instant<SystemClock>) {
+        self.initialized = true;
+        self.last_init_attempt = *now;
+        self.last_polled = *now;
+        self.retry_backoff = POLLING_INTERVAL;
+        self.consecutive_failures = 0;
+    }
+
+    pub fn init_failed(&mut self, now: &Instant<SystemClock>) {
+        self.initialized = false;
+        self.last_init_attempt = *now;
+        self.error_count = self.error_count.saturating_add(1);
+        self.retry_backoff =
+            Seconds((self.retry_backoff.0 * 2).clamp(POLLING_INTERVAL.0, MAX_RETRY_BACKOFF.0));
+    }
+
+    /// An initialized sensor is due for a read, once every POLLING_INTERVAL
+    pub fn read_due(&mut self, now: &Instant<SystemClock>) -> bool {
+        let due = self.initialized
+            && now
+                .checked_duration_since(&self.last_polled)
+                .map_or(false, |dur| dur >= POLLING_INTERVAL.into());
+        if due {
+            self.last_polled = *now;
+        }
+        due
+    }
+
+    pub fn read_succeeded(&mut self) {
+        self.consecutive_failures = 0;
+    }
+
+    /// Returns true if the driver is to be dropped and re-initialized
+    pub fn read_failed(&mut self) -> bool {
+        self.error_count = self.error_count.saturating_add(1);
+        self.consecutive_failures += 1;
+        if self.consecutive_failures >= MAX_CONSECUTIVE_FAILURES {
+            // Re-init (with bus recovery) on the next poll
+            self.initialized = false;
+            self.retry_backoff = Seconds(0);
+            true
+        } else {
+            false
+        }
+    }
 }
 
-impl<I2C, D> Sensor<I2C, D>
+pub struct Sensor<'a, I2C, F, D> {
+    bus: &'a I2cBus<I2C, F>,
+    drv: Option<Bme680<I2cProxy<'a, I2C>, D>>,
+    failures: FailureTracker,
+    warm_up: WarmUp,
+}
+
+impl<'a, I2C, F, D> Sensor<'a, I2C, F, D>
 where
     I2C: Read + Write,
+    <I2C as Read>::Error: fmt::Debug,
+    <I2C as Write>::Error: fmt::Debug,
+    F: FnMut(I2C) -> I2C,
     D: DelayMs<u8>,
 {
-    pub fn new(
-        i2c: I2C,
-        now: &Instant<SystemClock>,
+    /// A sensor that fails to initialize here is retried from `poll`
+    pub fn new(bus: &'a I2cBus<I2C, F>, now: &Instant<SystemClock>, delay: &mut D) -> Self {
+        let mut sensor = Sensor {
+            bus,
+            drv: None,
+            failures: FailureTracker::new(now),
+            warm_up: WarmUp::new(config::WARM_UP, now),
+        };
+        sensor.try_init(now, delay);
+        sensor
+    }
+
+    pub fn health(&self) -> SensorHealth {
+        self.failures.health()
+    }
+
+    /// Restarted whenever the sensor is re-initialized
+    pub fn warm_up(&self) -> &WarmUp {
+        &self.warm_up
+    }
+
+    /// Total number of failed reads and init attempts
+    pub fn error_count(&self) -> u32 {
+        self.failures.error_count()
+    }
+
+    pub fn poll(&mut self, now: &Instant<SystemClock>, delay: &mut D) -> Option<Measurement> {
+        if self.drv.is_none() {
+            if self.failures.init_due(now) {
+                self.try_init(now, delay);
+            }
+            return None;
+        }
+
+        if self.failures.read_due(now) {
+            match self.read(delay) {
+                Ok(data) => {
+                    self.failures.read_succeeded();
+                    return data.as_ref().map(Measurement::from);
+                }
+                Err(e) => {
+                    log::warn!("Sensor read err: {:?}", e);
+                    if self.failures.read_failed() {
+                        self.drv = None;
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn read(&mut self, delay: &mut D) -> Result<Option<FieldData>, DriverError<'a, I2C>> {
+        if let Some(drv) = &mut self.drv {
+            drv.set_sensor_mode(delay, PowerMode::ForcedMode)?;
+            let (data, state) = drv.get_sensor_data(delay)?;
+            if state == FieldDataCondition::NewData {
+                return Ok(Some(data));
+            }
+        }
+        Ok(None)
+    }
+
+    fn try_init(&mut self, now: &Instant<SystemClock>, delay: &mut D) {
+        // A reset mid-transfer can also leave the sensor holding SDA low
+        self.bus.recover();
+        match Self::init_driver(self.bus.proxy(), delay) {
+            Ok(drv) => {
+                self.drv.replace(drv);
+                self.warm_up.restart(now);
+                self.failures.init_succeeded(now);
+            }
+            Err(e) => {
+                log::warn!("Sensor init err: {:?}", e);
+                self.failures.init_failed(now);
+            }
+        }
+    }
+
+    fn init_driver(
+        i2c: I2cProxy<'a, I2C>,
         delay: &mut D,
-    ) -> Result<Self, Error<<I2C as Read>::Error, <I2C as Write>::Error>> {
+    ) -> Result<Bme680<I2cProxy<'a, I2C>, D>, DriverError<'a, I2C>> {
         let mut drv = Bme680::init(i2c, delay, I2CAddress::Secondary)?;
         let settings = SettingsBuilder::new()
             .with_humidity_oversampling(OversamplingSetting::OS2x)
@@ -47,27 +292,93 @@ where
             .build();
         drv.set_sensor_settings(delay, settings)?;
         drv.set_sensor_mode(delay, PowerMode::ForcedMode)?;
-        Ok(Sensor {
-            drv,
-            last_polled: *now,
-        })
+        Ok(drv)
     }
+}
 
-    pub fn poll(
-        &mut self,
-        now: &Instant<SystemClock>,
-        delay: &mut D,
-    ) -> Result<Option<FieldData>, Error<<I2C as Read>::Error, <I2C as Write>::Error>> {
-        if let Some(dur) = now.checked_duration_since(&self.last_polled) {
-            if dur >= POLLING_INTERVAL.into() {
-                self.last_polled = *now;
-                self.drv.set_sensor_mode(delay, PowerMode::ForcedMode)?;
-                let (data, state) = self.drv.get_sensor_data(delay)?;
-                if state == FieldDataCondition::NewData {
-                    return Ok(Some(data));
-                }
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(sec: u32) -> Instant<SystemClock> {
+        Instant::new(sec)
+    }
+
+    fn initialized() -> FailureTracker {
+        let mut failures = FailureTracker::new(&at(0));
+        failures.init_succeeded(&at(0));
+        failures
+    }
+
+    #[test]
+    fn reads_every_polling_interval() {
+        let mut failures = initialized();
+        assert_eq!(failures.health(), SensorHealth::Ok);
+        assert!(!failures.read_due(&at(14)));
+        assert!(failures.read_due(&at(15)));
+        assert!(!failures.read_due(&at(29)));
+        assert!(failures.read_due(&at(30)));
+    }
+
+    #[test]
+    fn consecutive_read_failures_drop_the_driver() {
+        let mut failures = initialized();
+        assert!(!failures.read_failed());
+        assert_eq!(failures.health(), SensorHealth::Degraded);
+        assert!(!failures.read_failed());
+        assert_eq!(failures.health(), SensorHealth::Degraded);
+        assert!(failures.read_failed());
+        assert_eq!(failures.health(), SensorHealth::Failed);
+        assert_eq!(failures.error_count(), 3);
+        assert!(!failures.read_due(&at(60)));
+        // Re-initialized right away
+        assert!(failures.init_due(&at(0)));
+    }
+
+    #[test]
+    fn successful_read_resets_the_failures() {
+        let mut failures = initialized();
+        assert!(!failures.read_failed());
+        assert!(!failures.read_failed());
+        failures.read_succeeded();
+        assert_eq!(failures.health(), SensorHealth::Ok);
+        assert!(!failures.read_failed());
+        assert!(!failures.read_failed());
+        assert_eq!(failures.health(), SensorHealth::Degraded);
+        assert_eq!(failures.error_count(), 4);
+    }
+
+    #[test]
+    fn init_retries_back_off() {
+        let mut failures = FailureTracker::new(&at(0));
+        assert_eq!(failures.health(), SensorHealth::Failed);
+        let mut now = 0;
+        for backoff in [30, 60, 120, 240, 480, 900, 900] {
+            failures.init_failed(&at(now));
+            assert!(!failures.init_due(&at(now + backoff - 1)));
+            assert!(failures.init_due(&at(now + backoff)));
+            now += backoff;
         }
-        Ok(None)
+        assert_eq!(failures.error_count(), 7);
+
+        failures.init_succeeded(&at(now));
+        assert_eq!(failures.health(), SensorHealth::Ok);
+        assert!(!failures.init_due(&at(now + 900)));
+        assert!(failures.read_due(&at(now + 15)));
+    }
+
+    #[test]
+    fn backoff_restarts_after_a_successful_init() {
+        let mut failures = FailureTracker::new(&at(0));
+        failures.init_failed(&at(0));
+        failures.init_failed(&at(30));
+        failures.init_succeeded(&at(90));
+        for _ in 0..MAX_CONSECUTIVE_FAILURES {
+            failures.read_failed();
+        }
+        assert!(failures.init_due(&at(90)));
+        failures.init_failed(&at(90));
+        assert!(!failures.init_due(&at(104)));
+        assert!(failures.init_due(&at(105)));
     }
 }
@@ -0,0 +1,218 @@
+//! Runtime settings, defaults come from config and are overridden by the
+//! settings file on the SD card
+//!
+//! The file has one `key value...` entry per line, unknown keys are ignored:
+//! ```text
+//! calibration.temperature <gain> <offset>
+//! units.temperature <C|F>
+//! units.pressure <hPa|inHg|mmHg>
+//! units.gas_resistance <ohm|kohm>
+//! time.clock <12h|24h>
+//! time.seconds <true|false>
+//! time.date <true|false>
+//! alarm.rule temperature outside 66 74 warning
+//! ```
+//!
+//! Any `alarm.rule` entries replace the default alarm rules, their
+//! thresholds are in the configured units. Calibration is always in the
+//! internal units (°C, %, hPa).
+
+use crate::alarm::{AlarmRule, MAX_RULES};
+use crate::calibration::{Calibration, Channel, ChannelCalibration};
+use crate::config;
+use crate::display::{ClockFormat, TimeFormat};
+use crate::units::{GasResistanceUnit, PressureUnit, TemperatureUnit, Units};
+use core::fmt::{self, Write};
+use heapless::{String, Vec};
+
+pub const SETTINGS_STRING_CAP: usize = 2048;
+
+#[derive(Clone, PartialEq, PartialOrd, Debug)]
+pub struct Settings {
+    pub calibration: Calibration,
+    pub alarm_rules: Vec<AlarmRule, MAX_RULES>,
+    pub units: Units,
+    pub time_format: TimeFormat,
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        Settings {
+            calibration: config::CALIBRATION,
+            alarm_rules: config::ALARM_RULES
+                .iter()
+                .take(MAX_RULES)
+                .copied()
+                .collect(),
+            units: config::UNITS,
+            time_format: config::TIME_FORMAT,
+        }
+    }
+
+    pub fn parse(&mut self, text: &str) {
+        // Units first, the alarm thresholds depend on them
+        for line in text.lines() {
+            let mut fields = line.split_whitespace();
+            let key = fields.next().unwrap_or("");
+            let symbol = fields.next().unwrap_or("");
+            match key {
+                "units.temperature" => {
+                    if let Some(u) = TemperatureUnit::ALL.iter().find(|u| u.symbol() == symbol) {
+                        self.units.temperature = *u;
+                    }
+                }
+                "units.pressure" => {
+                    if let Some(u) = PressureUnit::ALL.iter().find(|u| u.symbol() == symbol) {
+                        self.units.pressure = *u;
+                    }
+                }
+                "units.gas_resistance" => {
+                    if let Some(u) = GasResistanceUnit::ALL.iter().find(|u| u.symbol() == symbol) {
+                        self.units.gas_resistance = *u;
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let mut alarm_rules = Vec::new();
+        for line in text.lines() {
+            let mut fields = line.split_whitespace();
+            let key = fields.next().unwrap_or("");
+            if let Some(name) = key.strip_prefix("calibration.") {
+                let channel = Channel::ALL.iter().find(|c| c.name() == name);
+                let gain = fields.next().and_then(|f| f.parse::<f32>().ok());
+                let offset = fields.next().and_then(|f| f.parse::<f32>().ok());
+                if let (Some(channel), Some(gain), Some(offset)) = (channel, gain, offset) {
+                    *self.calibration.channel_mut(*channel) = ChannelCalibration { gain, offset };
+                }
+            } else if key == "time.clock" {
+                let name = fields.next().unwrap_or("");
+                if let Some(clock) = ClockFormat::ALL.iter().find(|c| c.name() == name) {
+                    self.time_format.clock = *clock;
+                }
+            } else if key == "time.seconds" {
+                if let Some(seconds) = fields.next().and_then(|f| f.parse::<bool>().ok()) {
+                    self.time_format.seconds = seconds;
+                }
+            } else if key == "time.date" {
+                if let Some(date) = fields.next().and_then(|f| f.parse::<bool>().ok()) {
+                    self.time_format.date = date;
+                }
+            } else if key == "alarm.rule" {
+                let rule = line
+                    .trim_start()
+                    .strip_prefix(key)
+                    .and_then(AlarmRule::parse);
+                if let Some(rule) = rule {
+                    // Rules beyond MAX_RULES are ignored
+                    let _ = alarm_rules.push(self.units.rule_to_internal(&rule));
+                }
+            }
+        }
+        if !alarm_rules.is_empty() {
+            self.alarm_rules = alarm_rules;
+        }
+    }
+
+    pub fn serialize(&self) -> Result<String<SETTINGS_STRING_CAP>, fmt::Error> {
+        let mut s = String::new();
+        for channel in Channel::ALL.iter() {
+            writeln!(
+                &mut s,
+                "calibration.{} {}",
+                channel.name(),
+                self.calibration.channel(*channel)
+            )?;
+        }
+        writeln!(
+            &mut s,
+            "units.temperature {}",
+            self.units.temperature.symbol()
+        )?;
+        writeln!(&mut s, "units.pressure {}", self.units.pressure.symbol())?;
+        writeln!(
+            &mut s,
+            "units.gas_resistance {}",
+            self.units.gas_resistance.symbol()
+        )?;
+        writeln!(&mut s, "time.clock {}", self.time_format.clock.name())?;
+        writeln!(&mut s, "time.seconds {}", self.time_format.seconds)?;
+        writeln!(&mut s, "time.date {}", self.time_format.date)?;
+        for rule in self.alarm_rules.iter() {
+            writeln!(&mut s, "alarm.rule {}", self.units.rule_from_internal(rule))?;
+        }
+        Ok(s)
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alarm::{Channel as AlarmChannel, Comparison, Severity};
+    use crate::units::TemperatureUnit;
+    use micromath::F32Ext;
+
+    #[test]
+    fn alarm_rule_lines_replace_the_defaults() {
+        let mut settings = Settings::new();
+        settings.parse(
+            "units.temperature C\n\
+             alarm.rule temperature above 30 warning\n\
+             \x20 alarm.rule humidity outside 30 60 critical hysteresis 2\n\
+             alarm.rule humidity sideways 30 info\n\
+             alarm.rules temperature below 10 info\n",
+        );
+        let rules: Vec<(AlarmChannel, Comparison, Severity), MAX_RULES> = settings
+            .alarm_rules
+            .iter()
+            .map(|r| (r.channel, r.comparison, r.severity))
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                (
+                    AlarmChannel::Temperature,
+                    Comparison::Above(30.0),
+                    Severity::Warning
+                ),
+                (
+                    AlarmChannel::Humidity,
+                    Comparison::Outside {
+                        low: 30.0,
+                        high: 60.0
+                    },
+                    Severity::Critical
+                ),
+            ]
+        );
+        assert_eq!(settings.alarm_rules[1].hysteresis, 2.0);
+    }
+
+    #[test]
+    fn alarm_rule_thresholds_are_in_the_configured_units() {
+        let mut settings = Settings::new();
+        settings
+            .parse("alarm.rule temperature above 86 warning hysteresis 1.8\nunits.temperature F\n");
+        assert_eq!(settings.units.temperature, TemperatureUnit::Fahrenheit);
+        let rule = settings.alarm_rules[0];
+        match rule.comparison {
+            Comparison::Above(threshold) => assert!((threshold - 30.0).abs() < 0.001),
+            other => panic!("{:?}", other),
+        }
+        assert!((rule.hysteresis - 1.0).abs() < 0.001);
+    }
+
+    #[test]
+    fn without_alarm_rule_lines_the_defaults_stay() {
+        let mut settings = Settings::new();
+        settings.parse("time.clock 24h\n");
+        assert_eq!(settings.alarm_rules, Settings::new().alarm_rules);
+    }
+}
@@ -0,0 +1,122 @@
+//! Sunrise and sunset times, NOAA approximation accurate to a few minutes
+
+use core::f32::consts::PI;
+use ds323x::{Datelike, NaiveDate};
+use micromath::F32Ext;
+
+#[derive(Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub struct Location {
+    /// Degrees, north positive
+    pub latitude: f32,
+    /// Degrees, east positive
+    pub longitude: f32,
+    /// Offset of the RTC's local time from UTC in minutes, the RTC doesn't
+    /// follow daylight saving time changes
+    pub utc_offset_min: i16,
+}
+
+impl Location {
+    /// Sunrise and sunset in minutes after local midnight, None on a polar
+    /// day or night
+    pub fn sunrise_sunset(&self, date: &NaiveDate) -> Option<(f32, f32)> {
+        // Fractional year (radians)
+        let gamma = 2.0 * PI / 365.0 * (date.ordinal0() as f32);
+        // Equation of time (minutes) and solar declination (radians)
+        let eq_time = 229.18
+            * (0.000075 + (0.001868 * gamma.cos())
+                - (0.032077 * gamma.sin())
+                - (0.014615 * (2.0 * gamma).cos())
+                - (0.040849 * (2.0 * gamma).sin()));
+        let decl = 0.006918 - (0.399912 * gamma.cos()) + (0.070257 * gamma.sin())
+            - (0.006758 * (2.0 * gamma).cos())
+            + (0.000907 * (2.0 * gamma).sin())
+            - (0.002697 * (3.0 * gamma).cos())
+            + (0.00148 * (3.0 * gamma).sin());
+
+        // Hour angle of the sun's upper limb on the horizon, with refraction
+        let lat = self.latitude.to_radians();
+        let cos_ha =
+            (90.833_f32.to_radians().cos() / (lat.cos() * decl.cos())) - (lat.tan() * decl.tan());
+        if !(-1.0..=1.0).contains(&cos_ha) {
+            return None;
+        }
+        let ha = cos_ha.acos().to_degrees();
+
+        let noon = 720.0 - (4.0 * self.longitude) - eq_time + f32::from(self.utc_offset_min);
+        Some((noon - (4.0 * ha), noon + (4.0 * ha)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Within 3 minutes of the almanac times
+    fn assert_times(location: &Location, date: NaiveDate, sunrise: (u8, u8), sunset: (u8, u8)) {
+        let (actual_sunrise, actual_sunset) = location.sunrise_sunset(&date).unwrap();
+        for (actual, (hour, minute)) in [(actual_sunrise, sunrise), (actual_sunset, sunset)] {
+            let expected = f32::from(hour) * 60.0 + f32::from(minute);
+            assert!(
+                (actual - expected).abs() <= 3.0,
+                "{} not within 3 minutes of {:02}:{:02}",
+                actual,
+                hour,
+                minute
+            );
+        }
+    }
+
+    const TROMSO: Location = Location {
+        latitude: 69.65,
+        longitude: 18.96,
+        utc_offset_min: 60,
+    };
+
+    #[test]
+    fn greenwich_midsummer() {
+        let greenwich = Location {
+            latitude: 51.477,
+            longitude: 0.0,
+            // BST
+            utc_offset_min: 60,
+        };
+        assert_times(
+            &greenwich,
+            NaiveDate::from_ymd(2021, 6, 21),
+            (4, 43),
+            (21, 21),
+        );
+    }
+
+    #[test]
+    fn new_york_midwinter() {
+        let new_york = Location {
+            latitude: 40.713,
+            longitude: -74.006,
+            // EST
+            utc_offset_min: -300,
+        };
+        assert_times(
+            &new_york,
+            NaiveDate::from_ymd(2021, 12, 21),
+            (7, 17),
+            (16, 32),
+        );
+    }
+
+    #[test]
+    fn polar_night_and_day() {
+        assert_eq!(
+            TROMSO.sunrise_sunset(&NaiveDate::from_ymd(2021, 12, 21)),
+            None
+        );
+        assert_eq!(
+            TROMSO.sunrise_sunset(&NaiveDate::from_ymd(2021, 6, 21)),
+            None
+        );
+        // The sun rises again by early February
+        assert!(TROMSO
+            .sunrise_sunset(&NaiveDate::from_ymd(2021, 2, 1))
+            .is_some());
+    }
+}
@@ -1,25 +1,33 @@
-use crate::hal::{rcc::Clocks, stm32::SYST};
 use core::sync::atomic::{AtomicU32, Ordering::SeqCst};
-use cortex_m::peripheral::syst::SystClkSource;
+use cortex_m::peripheral::{syst::SystClkSource, SYST};
 use ds323x::{Datelike, Timelike};
 use embedded_time::{clock, fraction::Fraction, Clock, Instant};
 
-/// 32-bit second clock
+/// SysTick interrupt rate
+pub const TICK_HZ: u32 = 100;
+
+/// 32-bit second clock, with a finer tick counter for timing that needs
+/// more resolution than the seconds (buzzer patterns)
 #[derive(Debug)]
-#[repr(transparent)]
-pub struct SystemClock(AtomicU32);
+pub struct SystemClock {
+    seconds: AtomicU32,
+    ticks: AtomicU32,
+}
 
 impl SystemClock {
     pub const fn new() -> Self {
-        SystemClock(AtomicU32::new(0))
+        SystemClock {
+            seconds: AtomicU32::new(0),
+            ticks: AtomicU32::new(0),
+        }
     }
 
-    pub fn enable_systick_interrupt(&self, mut syst: SYST, clocks: &Clocks) {
-        log::debug!("Enable SystemClock hclk freq {} Hz", clocks.hclk().0);
+    pub fn enable_systick_interrupt(&self, mut syst: SYST, hclk_hz: u32) {
+        log::debug!("Enable SystemClock hclk freq {} Hz", hclk_hz);
 
-        // Generate an interrupt once a second, HCLK/8
+        // Generate an interrupt TICK_HZ times a second, HCLK/8
         syst.set_clock_source(SystClkSource::External);
-        syst.set_reload(clocks.hclk().0 / 8);
+        syst.set_reload((hclk_hz / 8 / TICK_HZ) - 1);
         syst.clear_current();
         syst.enable_counter();
         syst.enable_interrupt();
@@ -29,11 +37,20 @@ impl SystemClock {
     }
 
     pub fn inc_from_interrupt(&self) {
-        self.0.fetch_add(1, SeqCst);
+        let ticks = self.ticks.fetch_add(1, SeqCst).wrapping_add(1);
+        if ticks % TICK_HZ == 0 {
+            self.seconds.fetch_add(1, SeqCst);
+        }
     }
 
+    /// Seconds since the SysTick interrupt was enabled
     pub fn get_raw(&self) -> u32 {
-        self.0.load(SeqCst)
+        self.seconds.load(SeqCst)
+    }
+
+    /// Ticks (1 / TICK_HZ seconds) since the SysTick interrupt was enabled, wraps
+    pub fn ticks(&self) -> u32 {
+        self.ticks.load(SeqCst)
     }
 
     pub fn now(&self) -> Instant<Self> {
@@ -41,6 +58,12 @@ impl SystemClock {
     }
 }
 
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Clock for SystemClock {
     type T = u32;
     const SCALING_FACTOR: Fraction = Fraction::new(1, 1);
@@ -1,14 +1,18 @@
 use crate::alarm::AlarmStatus;
+use crate::sensor::SensorHealth;
 
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
 pub struct SystemStatus {
     pub uptime_sec: u32,
     pub alarm: AlarmStatus,
-    pub alarm_warmed_up: bool,
+    /// Countdown until every sensor channel has settled
+    pub warm_up_remaining_sec: u32,
     pub record_count: u32,
     pub storage_connected: bool,
     pub storage_full: bool,
     pub storage_error: bool,
+    pub sensor_health: SensorHealth,
+    pub sensor_errors: u32,
 }
 
 impl SystemStatus {
@@ -0,0 +1,206 @@
+//! Units for the display, the records and the settings file
+//!
+//! Values are kept in the internal units (°C, %, hPa, Ω) and only converted
+//! on output, or on input for alarm thresholds.
+
+use crate::alarm::{AlarmRule, Channel, Comparison};
+use core::fmt;
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum TemperatureUnit {
+    Celsius,
+    Fahrenheit,
+}
+
+impl TemperatureUnit {
+    pub const ALL: [TemperatureUnit; 2] = [TemperatureUnit::Celsius, TemperatureUnit::Fahrenheit];
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "C",
+            TemperatureUnit::Fahrenheit => "F",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum PressureUnit {
+    Hectopascal,
+    InchOfMercury,
+    MillimeterOfMercury,
+}
+
+impl PressureUnit {
+    pub const ALL: [PressureUnit; 3] = [
+        PressureUnit::Hectopascal,
+        PressureUnit::InchOfMercury,
+        PressureUnit::MillimeterOfMercury,
+    ];
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            PressureUnit::Hectopascal => "hPa",
+            PressureUnit::InchOfMercury => "inHg",
+            PressureUnit::MillimeterOfMercury => "mmHg",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum GasResistanceUnit {
+    Ohm,
+    Kiloohm,
+}
+
+impl GasResistanceUnit {
+    pub const ALL: [GasResistanceUnit; 2] = [GasResistanceUnit::Ohm, GasResistanceUnit::Kiloohm];
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            GasResistanceUnit::Ohm => "ohm",
+            GasResistanceUnit::Kiloohm => "kohm",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct Units {
+    pub temperature: TemperatureUnit,
+    pub pressure: PressureUnit,
+    pub gas_resistance: GasResistanceUnit,
+}
+
+impl Units {
+    /// Convert from the channel's internal unit
+    pub fn convert(&self, channel: Channel, value: f32) -> f32 {
+        let (scale, offset) = self.scale_offset(channel);
+        (value * scale) + offset
+    }
+
+    /// Convert a difference, e.g. a rate or a hysteresis band
+    pub fn convert_difference(&self, channel: Channel, value: f32) -> f32 {
+        value * self.scale_offset(channel).0
+    }
+
+    /// Convert to the channel's internal unit
+    pub fn to_internal(&self, channel: Channel, value: f32) -> f32 {
+        let (scale, offset) = self.scale_offset(channel);
+        (value - offset) / scale
+    }
+
+    pub fn to_internal_difference(&self, channel: Channel, value: f32) -> f32 {
+        value / self.scale_offset(channel).0
+    }
+
+    /// Unit label, empty for unitless channels
+    pub fn symbol(&self, channel: Channel) -> &'static str {
+        match channel {
+            Channel::Temperature
+            | Channel::DewPoint
+            | Channel::DewPointSpread
+            | Channel::HeatIndex => self.temperature.symbol(),
+            Channel::Humidity => "%",
+            Channel::Pressure | Channel::SeaLevelPressure => self.pressure.symbol(),
+            Channel::GasResistance => self.gas_resistance.symbol(),
+            Channel::AbsoluteHumidity => "g/m3",
+            Channel::Humidex => "",
+        }
+    }
+
+    /// Decimal places that keep the sensor's resolution
+    pub fn decimals(&self, channel: Channel) -> usize {
+        match channel {
+            Channel::Pressure | Channel::SeaLevelPressure
+                if self.pressure == PressureUnit::InchOfMercury =>
+            {
+                3
+            }
+            Channel::GasResistance => match self.gas_resistance {
+                GasResistanceUnit::Ohm => 0,
+                GasResistanceUnit::Kiloohm => 3,
+            },
+            _ => 2,
+        }
+    }
+
+    /// The rule with its thresholds and hysteresis in these units
+    pub fn rule_from_internal(&self, rule: &AlarmRule) -> AlarmRule {
+        self.map_rule(rule, Units::convert, Units::convert_difference)
+    }
+
+    /// The rule with its thresholds and hysteresis in the internal units
+    pub fn rule_to_internal(&self, rule: &AlarmRule) -> AlarmRule {
+        self.map_rule(rule, Units::to_internal, Units::to_internal_difference)
+    }
+
+    fn map_rule(
+        &self,
+        rule: &AlarmRule,
+        value: fn(&Units, Channel, f32) -> f32,
+        difference: fn(&Units, Channel, f32) -> f32,
+    ) -> AlarmRule {
+        let channel = rule.channel;
+        // Rate rules compare a change
+        let threshold = |v: f32| {
+            if rule.rate_window.is_some() {
+                difference(self, channel, v)
+            } else {
+                value(self, channel, v)
+            }
+        };
+        let comparison = match rule.comparison {
+            Comparison::Above(v) => Comparison::Above(threshold(v)),
+            Comparison::Below(v) => Comparison::Below(threshold(v)),
+            Comparison::Outside { low, high } => Comparison::Outside {
+                low: threshold(low),
+                high: threshold(high),
+            },
+        };
+        AlarmRule {
+            comparison,
+            hysteresis: difference(self, channel, rule.hysteresis),
+            ..*rule
+        }
+    }
+
+    /// Output = (internal * scale) + offset
+    fn scale_offset(&self, channel: Channel) -> (f32, f32) {
+        match channel {
+            Channel::Temperature | Channel::DewPoint | Channel::HeatIndex => {
+                match self.temperature {
+                    TemperatureUnit::Celsius => (1.0, 0.0),
+                    TemperatureUnit::Fahrenheit => (1.8, 32.0),
+                }
+            }
+            // A temperature difference
+            Channel::DewPointSpread => match self.temperature {
+                TemperatureUnit::Celsius => (1.0, 0.0),
+                TemperatureUnit::Fahrenheit => (1.8, 0.0),
+            },
+            Channel::Pressure | Channel::SeaLevelPressure => match self.pressure {
+                PressureUnit::Hectopascal => (1.0, 0.0),
+                PressureUnit::InchOfMercury => (0.029_53, 0.0),
+                PressureUnit::MillimeterOfMercury => (0.750_062, 0.0),
+            },
+            Channel::GasResistance => match self.gas_resistance {
+                GasResistanceUnit::Ohm => (1.0, 0.0),
+                GasResistanceUnit::Kiloohm => (0.001, 0.0),
+            },
+            Channel::Humidity | Channel::AbsoluteHumidity | Channel::Humidex => (1.0, 0.0),
+        }
+    }
+}
+
+/// `temperature F, pressure hPa, gas_resistance ohm`, the form of the
+/// records' units header
+impl fmt::Display for Units {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "temperature {}, pressure {}, gas_resistance {}",
+            self.temperature.symbol(),
+            self.pressure.symbol(),
+            self.gas_resistance.symbol()
+        )
+    }
+}
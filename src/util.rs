@@ -30,3 +30,8 @@ impl fmt::Display for DisplayBool {
 pub fn celsius_to_fahrenheit(c: f32) -> f32 {
     (c * 1.8) + 32.0
 }
+
+/// Convert degrees fahrenheit (°F) to celsius (°C)
+pub fn fahrenheit_to_celsius(f: f32) -> f32 {
+    (f - 32.0) / 1.8
+}
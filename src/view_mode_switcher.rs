@@ -1,63 +1,248 @@
+use crate::display::View;
 use crate::system_clock::SystemClock;
 use embedded_time::{duration::Seconds, Instant};
 
-const VIEW_DURATION: Seconds = Seconds(5_u32);
+/// Duration of a view shown by set_mode that isn't in the rotation
+const DEFAULT_VIEW_DURATION: Seconds = Seconds(5_u32);
 
-#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
-pub enum ViewMode {
-    Time,
-    Date,
-    SensorReadings,
-    SystemStatus,
+/// A view in the rotation and how long it's shown
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct ViewSlot {
+    pub view: View,
+    pub duration: Seconds,
 }
 
-impl ViewMode {
-    fn next(self) -> Self {
-        match self {
-            ViewMode::Time => ViewMode::Date,
-            ViewMode::Date => ViewMode::SensorReadings,
-            ViewMode::SensorReadings => ViewMode::SystemStatus,
-            ViewMode::SystemStatus => ViewMode::Time,
-        }
-    }
-}
-
-impl Default for ViewMode {
-    fn default() -> Self {
-        ViewMode::Time
+impl ViewSlot {
+    pub const fn new(view: View, duration: Seconds) -> Self {
+        ViewSlot { view, duration }
     }
 }
 
+/// Cycles through the rotation, or stays on the pinned view. Views shown by
+/// set_mode interrupt either for their duration, a preempting view is shown
+/// ahead of both until released.
 pub struct ViewModeSwitcher {
-    mode: ViewMode,
+    rotation: &'static [ViewSlot],
+    pinned: Option<View>,
+    /// Position in the rotation
+    index: usize,
+    interrupt: Option<View>,
+    preempt: Option<View>,
     last_transition: Instant<SystemClock>,
 }
 
 impl ViewModeSwitcher {
-    pub fn new(now: Instant<SystemClock>) -> Self {
+    pub fn new(
+        rotation: &'static [ViewSlot],
+        pinned: Option<View>,
+        now: Instant<SystemClock>,
+    ) -> Self {
         ViewModeSwitcher {
-            mode: Default::default(),
+            rotation,
+            pinned,
+            index: 0,
+            interrupt: None,
+            preempt: None,
             last_transition: now,
         }
     }
 
+    /// Call when the current view has nothing to show. A pinned view falls
+    /// back to the rotation until it's available again.
     pub fn skip(&mut self, now: &Instant<SystemClock>) {
         self.last_transition = *now;
-        self.mode = self.mode.next();
+        if self.interrupt.take().is_none() {
+            self.advance();
+            if self.pinned.is_some() {
+                self.interrupt = self.rotation.get(self.index).map(|slot| slot.view);
+            }
+        }
     }
 
-    pub fn set_mode(&mut self, mode: ViewMode, now: &Instant<SystemClock>) {
-        self.mode = mode;
+    /// Show the view for its duration, then resume the rotation where it
+    /// was interrupted
+    pub fn set_mode(&mut self, view: View, now: &Instant<SystemClock>) {
+        self.interrupt.replace(view);
         self.last_transition = *now;
     }
 
-    pub fn mode(&mut self, now: &Instant<SystemClock>) -> ViewMode {
+    /// Show the view until released, set_mode views wait behind it
+    pub fn preempt(&mut self, view: View) {
+        self.preempt.replace(view);
+    }
+
+    /// Resume with the interrupting or rotation view, shown for its full
+    /// duration
+    pub fn release(&mut self, now: &Instant<SystemClock>) {
+        if self.preempt.take().is_some() {
+            self.last_transition = *now;
+        }
+    }
+
+    pub fn mode(&mut self, now: &Instant<SystemClock>) -> View {
+        if let Some(view) = self.preempt {
+            return view;
+        }
         if let Some(dur) = now.checked_duration_since(&self.last_transition) {
-            if dur >= VIEW_DURATION.into() {
+            if dur >= self.duration().into() {
                 self.last_transition = *now;
-                self.mode = self.mode.next();
+                if self.interrupt.take().is_none() && self.pinned.is_none() {
+                    self.advance();
+                }
             }
         }
-        self.mode
+        self.current()
+    }
+
+    fn current(&self) -> View {
+        self.interrupt
+            .or(self.pinned)
+            .or_else(|| self.rotation.get(self.index).map(|slot| slot.view))
+            .unwrap_or(View::TIME)
+    }
+
+    fn duration(&self) -> Seconds {
+        let view = self.current();
+        let slot = match self.interrupt.or(self.pinned) {
+            Some(_) => self.rotation.iter().find(|slot| slot.view == view),
+            None => self.rotation.get(self.index),
+        };
+        slot.map_or(DEFAULT_VIEW_DURATION, |slot| slot.duration)
+    }
+
+    fn advance(&mut self) {
+        if !self.rotation.is_empty() {
+            self.index = (self.index + 1) % self.rotation.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::{Frame, RenderError, Renderer, ViewContext};
+    use core::convert::Infallible;
+
+    fn render_nothing(
+        _: &mut Renderer,
+        _: &mut Frame,
+        _: &ViewContext,
+    ) -> Result<bool, RenderError<Infallible>> {
+        Ok(true)
+    }
+
+    const A: View = View::new("a", render_nothing);
+    const B: View = View::new("b", render_nothing);
+    const C: View = View::new("c", render_nothing);
+    const STATUS: View = View::new("status", render_nothing);
+    const ALERT: View = View::new("alert", render_nothing);
+
+    const ROTATION: &[ViewSlot] = &[
+        ViewSlot::new(A, Seconds(5_u32)),
+        ViewSlot::new(B, Seconds(10_u32)),
+        ViewSlot::new(C, Seconds(5_u32)),
+    ];
+
+    fn at(sec: u32) -> Instant<SystemClock> {
+        Instant::new(sec)
+    }
+
+    #[test]
+    fn rotation_shows_each_view_for_its_duration() {
+        let mut switcher = ViewModeSwitcher::new(ROTATION, None, at(0));
+        assert_eq!(switcher.mode(&at(0)), A);
+        assert_eq!(switcher.mode(&at(4)), A);
+        assert_eq!(switcher.mode(&at(5)), B);
+        assert_eq!(switcher.mode(&at(14)), B);
+        assert_eq!(switcher.mode(&at(15)), C);
+        assert_eq!(switcher.mode(&at(20)), A);
+    }
+
+    #[test]
+    fn skip_advances_and_restarts_the_duration() {
+        let mut switcher = ViewModeSwitcher::new(ROTATION, None, at(0));
+        assert_eq!(switcher.mode(&at(2)), A);
+        switcher.skip(&at(2));
+        assert_eq!(switcher.mode(&at(2)), B);
+        assert_eq!(switcher.mode(&at(11)), B);
+        assert_eq!(switcher.mode(&at(12)), C);
+        switcher.skip(&at(13));
+        assert_eq!(switcher.mode(&at(13)), A);
+    }
+
+    #[test]
+    fn set_mode_interrupts_then_resumes_the_rotation() {
+        let mut switcher = ViewModeSwitcher::new(ROTATION, None, at(0));
+        assert_eq!(switcher.mode(&at(2)), A);
+        switcher.set_mode(STATUS, &at(2));
+        // Not in the rotation, shown for the default duration
+        assert_eq!(switcher.mode(&at(2)), STATUS);
+        assert_eq!(switcher.mode(&at(6)), STATUS);
+        // The interrupted view, for its full duration
+        assert_eq!(switcher.mode(&at(7)), A);
+        assert_eq!(switcher.mode(&at(11)), A);
+        assert_eq!(switcher.mode(&at(12)), B);
+    }
+
+    #[test]
+    fn set_mode_uses_the_rotation_duration() {
+        let mut switcher = ViewModeSwitcher::new(ROTATION, None, at(0));
+        switcher.set_mode(B, &at(0));
+        assert_eq!(switcher.mode(&at(9)), B);
+        assert_eq!(switcher.mode(&at(10)), A);
+    }
+
+    #[test]
+    fn skip_ends_a_set_mode_view() {
+        let mut switcher = ViewModeSwitcher::new(ROTATION, None, at(0));
+        switcher.set_mode(STATUS, &at(0));
+        switcher.skip(&at(1));
+        assert_eq!(switcher.mode(&at(1)), A);
+        assert_eq!(switcher.mode(&at(6)), B);
+    }
+
+    #[test]
+    fn pinned_view_stays_and_the_rotation_stands_in_when_skipped() {
+        let mut switcher = ViewModeSwitcher::new(ROTATION, Some(C), at(0));
+        assert_eq!(switcher.mode(&at(0)), C);
+        assert_eq!(switcher.mode(&at(100)), C);
+        switcher.skip(&at(100));
+        assert_eq!(switcher.mode(&at(100)), B);
+        assert_eq!(switcher.mode(&at(109)), B);
+        assert_eq!(switcher.mode(&at(110)), C);
+
+        switcher.set_mode(STATUS, &at(120));
+        assert_eq!(switcher.mode(&at(124)), STATUS);
+        assert_eq!(switcher.mode(&at(125)), C);
+    }
+
+    #[test]
+    fn preempt_holds_until_released() {
+        let mut switcher = ViewModeSwitcher::new(ROTATION, None, at(0));
+        switcher.preempt(ALERT);
+        assert_eq!(switcher.mode(&at(0)), ALERT);
+        // Waits behind the preempting view
+        switcher.set_mode(STATUS, &at(1));
+        assert_eq!(switcher.mode(&at(100)), ALERT);
+
+        switcher.release(&at(100));
+        assert_eq!(switcher.mode(&at(104)), STATUS);
+        assert_eq!(switcher.mode(&at(105)), A);
+    }
+
+    #[test]
+    fn release_without_preempt_keeps_the_duration() {
+        let mut switcher = ViewModeSwitcher::new(ROTATION, None, at(0));
+        assert_eq!(switcher.mode(&at(0)), A);
+        switcher.release(&at(3));
+        assert_eq!(switcher.mode(&at(5)), B);
+    }
+
+    #[test]
+    fn empty_rotation_shows_the_time() {
+        let mut switcher = ViewModeSwitcher::new(&[], None, at(0));
+        assert_eq!(switcher.mode(&at(0)), View::TIME);
+        switcher.skip(&at(1));
+        assert_eq!(switcher.mode(&at(10)), View::TIME);
     }
 }
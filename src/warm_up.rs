@@ -0,0 +1,160 @@
+//! Per-quantity warm-up after the sensor is (re-)initialized, readings are
+//! recorded but marked as settling and don't trigger alarms until then
+
+use crate::system_clock::SystemClock;
+use core::convert::TryFrom;
+use embedded_time::{duration::Seconds, Instant};
+use heapless::Vec;
+
+/// A quantity measured by the sensor, each warms up on its own
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Quantity {
+    Temperature,
+    Humidity,
+    Pressure,
+    GasResistance,
+}
+
+impl Quantity {
+    pub const ALL: [Quantity; 4] = [
+        Quantity::Temperature,
+        Quantity::Humidity,
+        Quantity::Pressure,
+        Quantity::GasResistance,
+    ];
+
+    /// Marks the quantity in the records' settling column
+    pub fn symbol(self) -> char {
+        match self {
+            Quantity::Temperature => 't',
+            Quantity::Humidity => 'h',
+            Quantity::Pressure => 'p',
+            Quantity::GasResistance => 'g',
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub struct WarmUpConfig {
+    pub temperature: Seconds,
+    pub humidity: Seconds,
+    pub pressure: Seconds,
+    /// The gas heater takes the longest to stabilize
+    pub gas_resistance: Seconds,
+}
+
+impl WarmUpConfig {
+    fn duration(&self, quantity: Quantity) -> Seconds {
+        match quantity {
+            Quantity::Temperature => self.temperature,
+            Quantity::Humidity => self.humidity,
+            Quantity::Pressure => self.pressure,
+            Quantity::GasResistance => self.gas_resistance,
+        }
+    }
+
+    fn longest(&self) -> Seconds {
+        Seconds(
+            self.temperature
+                .0
+                .max(self.humidity.0)
+                .max(self.pressure.0)
+                .max(self.gas_resistance.0),
+        )
+    }
+}
+
+pub struct WarmUp {
+    config: WarmUpConfig,
+    since: Instant<SystemClock>,
+}
+
+impl WarmUp {
+    pub fn new(config: WarmUpConfig, now: &Instant<SystemClock>) -> Self {
+        WarmUp {
+            config,
+            since: *now,
+        }
+    }
+
+    /// Call when the sensor is (re-)initialized
+    pub fn restart(&mut self, now: &Instant<SystemClock>) {
+        self.since = *now;
+    }
+
+    pub fn is_settled(&self, quantity: Quantity, now: &Instant<SystemClock>) -> bool {
+        self.remaining(self.config.duration(quantity), now) == Seconds(0)
+    }
+
+    /// Every one of the quantities has settled, e.g. those a derived value
+    /// depends on
+    pub fn are_settled(&self, quantities: &[Quantity], now: &Instant<SystemClock>) -> bool {
+        quantities.iter().all(|q| self.is_settled(*q, now))
+    }
+
+    /// The quantities still warming up
+    pub fn settling(&self, now: &Instant<SystemClock>) -> Vec<Quantity, 4> {
+        Quantity::ALL
+            .iter()
+            .copied()
+            .filter(|q| !self.is_settled(*q, now))
+            .collect()
+    }
+
+    /// Countdown until every quantity has settled
+    pub fn remaining_sec(&self, now: &Instant<SystemClock>) -> u32 {
+        self.remaining(self.config.longest(), now).0
+    }
+
+    fn remaining(&self, duration: Seconds, now: &Instant<SystemClock>) -> Seconds {
+        let elapsed = now
+            .checked_duration_since(&self.since)
+            .and_then(|dur| Seconds::<u32>::try_from(dur).ok())
+            .unwrap_or(Seconds(0));
+        Seconds(duration.0.saturating_sub(elapsed.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: WarmUpConfig = WarmUpConfig {
+        temperature: Seconds(120),
+        humidity: Seconds(120),
+        pressure: Seconds(60),
+        gas_resistance: Seconds(600),
+    };
+
+    #[test]
+    fn quantities_settle_on_their_own() {
+        let warm_up = WarmUp::new(CONFIG, &Instant::new(1000));
+        assert_eq!(warm_up.settling(&Instant::new(1000)), Quantity::ALL);
+        assert_eq!(
+            warm_up.settling(&Instant::new(1060)),
+            [
+                Quantity::Temperature,
+                Quantity::Humidity,
+                Quantity::GasResistance
+            ]
+        );
+        assert_eq!(
+            warm_up.settling(&Instant::new(1120)),
+            [Quantity::GasResistance]
+        );
+        assert!(warm_up.are_settled(
+            &[Quantity::Temperature, Quantity::Pressure],
+            &Instant::new(1120)
+        ));
+        assert_eq!(warm_up.remaining_sec(&Instant::new(1120)), 480);
+        assert!(warm_up.settling(&Instant::new(1600)).is_empty());
+    }
+
+    #[test]
+    fn restart() {
+        let mut warm_up = WarmUp::new(CONFIG, &Instant::new(0));
+        warm_up.restart(&Instant::new(1000));
+        assert!(!warm_up.is_settled(Quantity::Pressure, &Instant::new(1030)));
+        assert!(warm_up.is_settled(Quantity::Pressure, &Instant::new(1060)));
+    }
+}